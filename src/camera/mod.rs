@@ -3,9 +3,12 @@ use bevy::{
     prelude::*, window::{PrimaryWindow, CursorGrabMode},
 };
 
+use bevy_rapier3d::prelude::RapierContext;
 use std::f32::consts::PI;
 
-use crate::{player::{self, MainPlayer, Player}, AppState};
+use crate::{map::colliders, player::{self, MainPlayer, Player}, AppState, input::gamepad::{ActiveGamepad, GamepadConfig, read_stick}, ui::{screen_open, OpenScreens}};
+
+pub mod spectator;
 
 pub struct ThirdPersonCameraPlugin;
 
@@ -16,11 +19,13 @@ impl Plugin for ThirdPersonCameraPlugin {
         .register_type::<Zoom>()
         .register_type::<ThirdPersonCamera>()
         .register_type::<ThirdPersonCameraTarget>()
+        .add_plugins(spectator::SpectatorPlugin)
         .add_systems(Update, (
-            orbit_mouse.run_if(orbit_condition),
-            sync_player_camera.after(orbit_mouse),
-            toggle_cursor,
-        ).run_if(in_state(AppState::InGame)));
+            (orbit_mouse, orbit_gamepad).run_if(orbit_condition).run_if(not(screen_open)),
+            sync_player_camera.after(orbit_mouse).after(orbit_gamepad),
+        ).run_if(not(spectator::is_spectating)).run_if(in_state(AppState::InGame)))
+        .add_systems(Update, grab_cursor.run_if(in_state(AppState::InGame)))
+        .add_systems(OnExit(AppState::InGame), release_cursor);
     }
 }
 
@@ -34,6 +39,7 @@ pub struct ThirdPersonCamera {
     pub zoom_sensitivity: f32,
     pub focus: Vec3,
     pub mouse_sensitivity: f32,
+    pub invert_y: bool,
     pub snap_mouse: bool,
 }
 
@@ -48,6 +54,7 @@ impl Default for ThirdPersonCamera {
             zoom_sensitivity: 1.0,
             focus: Vec3::new(0.0, 0.3, -0.1),
             mouse_sensitivity: 2.0,
+            invert_y: false,
             snap_mouse: true,
         }
     }
@@ -94,6 +101,11 @@ impl Zoom {
             radius_copy: None,
         }
     }
+
+    /// the distance of the camera before walls pull it in
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
 }
 
 
@@ -104,6 +116,7 @@ pub struct ThirdPersonCameraTarget;
 fn sync_player_camera(
     player_query: Query<(&Transform, &Player), With<ThirdPersonCameraTarget>>, // query all players with an ThirdPersonCameraTarget as Component
     mut camera_query: Query<(&mut ThirdPersonCamera, &mut Transform), Without<ThirdPersonCameraTarget>>, // get all ThirdPersonCameras
+    rapier: Res<RapierContext>,
 ) {
 
     for (player, player_details) in player_query.iter() {
@@ -125,7 +138,17 @@ fn sync_player_camera(
             //info!("look_at: {:?}", cam.focus);
 
             let delta = player.translation + cam.focus;
-            cam_t.translation = desired_translation + delta;
+
+            // spring arm, walls between the player and the camera pull the camera in
+            let mut arm = desired_translation;
+            let length = arm.length();
+            if length > 0.0 {
+                if let Some((_, toi)) = rapier.cast_ray(delta, arm / length, length, true, colliders::camera_filter()) {
+                    arm *= ((toi - 0.2) / length).max(0.1);
+                }
+            }
+
+            cam_t.translation = delta + arm;
             //info!("cam_t.translation: {:?}", cam_t.translation);
         }
     }
@@ -159,19 +182,9 @@ pub fn orbit_mouse(
             delta
         };
 
-        let delta_y = rotation.y / window.height() * PI;
-        let yaw = Quat::from_rotation_y(-delta_x);
-        let pitch = Quat::from_rotation_x(-delta_y);
-        cam_transform.rotation = yaw * cam_transform.rotation; // rotate around global y axis
-
-        // Calculate the new rotation without applying it to the camera yet
-        let new_rotation = cam_transform.rotation * pitch;
-
-        // check if new rotation will cause camera to go beyond the 180 degree vertical bounds
-        let up_vector = new_rotation * Vec3::Y;
-        if up_vector.y > 0.0 {
-            cam_transform.rotation = new_rotation;
-        }
+        let invert = if cam.invert_y { -1.0 } else { 1.0 };
+        let delta_y = rotation.y * invert / window.height() * PI;
+        rotate_camera(&mut cam_transform, delta_x, delta_y);
     }
 
     let rot_matrix = Mat3::from_quat(cam_transform.rotation);
@@ -181,24 +194,90 @@ pub fn orbit_mouse(
         cam.focus + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, cam.zoom.radius));
 }
 
-fn toggle_cursor(
+/// orbit the camera with the right stick of the active gamepad,
+/// the speed ramps up while the stick is held, so small corrections stay precise
+fn orbit_gamepad(
+    time: Res<Time>,
+    active_gamepad: Res<ActiveGamepad>,
+    config: Res<GamepadConfig>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut cam_q: Query<(&ThirdPersonCamera, &mut Transform)>,
+    mut acceleration: Local<f32>,
+) {
+    let Some(gamepad) = active_gamepad.gamepad else { return };
+    let Ok((cam, mut cam_transform)) = cam_q.get_single_mut() else { return };
+
+    let stick = read_stick(
+        &axes,
+        gamepad,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+        config.look_deadzone,
+    );
+
+    if stick == Vec2::ZERO {
+        *acceleration = 0.0;
+        return;
+    }
+
+    if config.look_acceleration_time > 0.0 {
+        *acceleration = (*acceleration + time.delta_seconds() / config.look_acceleration_time).min(1.0);
+    } else {
+        *acceleration = 1.0;
+    }
+
+    // ease in, so the first frames only move the camera a little bit
+    let factor = *acceleration * *acceleration;
+    let invert = if cam.invert_y { -1.0 } else { 1.0 };
+
+    let delta_x = stick.x * config.look_speed.x * factor * time.delta_seconds();
+    let delta_y = -stick.y * invert * config.look_speed.y * factor * time.delta_seconds();
+    rotate_camera(&mut cam_transform, delta_x, delta_y);
+}
+
+fn rotate_camera(cam_transform: &mut Transform, delta_x: f32, delta_y: f32) {
+    let yaw = Quat::from_rotation_y(-delta_x);
+    let pitch = Quat::from_rotation_x(-delta_y);
+    cam_transform.rotation = yaw * cam_transform.rotation; // rotate around global y axis
+
+    // Calculate the new rotation without applying it to the camera yet
+    let new_rotation = cam_transform.rotation * pitch;
+
+    // check if new rotation will cause camera to go beyond the 180 degree vertical bounds
+    let up_vector = new_rotation * Vec3::Y;
+    if up_vector.y > 0.0 {
+        cam_transform.rotation = new_rotation;
+    }
+}
+
+/// the window catches the cursor while playing, every screen over the game releases it
+fn grab_cursor(
     mut cam_q: Query<&mut ThirdPersonCamera>,
-    keys: Res<Input<KeyCode>>,
+    screens: OpenScreens,
     mut window_q: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     let Ok(mut cam) = cam_q.get_single_mut() else { return };
 
-    if keys.just_pressed(KeyCode::Escape) {
-        cam.snap_mouse = !cam.snap_mouse;
+    let snap_mouse = !screens.any();
+    if cam.snap_mouse != snap_mouse {
+        cam.snap_mouse = snap_mouse;
     }
 
     let mut window = window_q.get_single_mut().unwrap();
-    if cam.snap_mouse {
-        window.cursor.grab_mode = CursorGrabMode::Locked;
-        window.cursor.visible = false;
-    } else {
-        window.cursor.grab_mode = CursorGrabMode::None;
-        window.cursor.visible = true;
+    set_cursor_grab(&mut window, cam.snap_mouse);
+}
+
+/// the menus after a match need the cursor
+fn release_cursor(mut window_q: Query<&mut Window, With<PrimaryWindow>>) {
+    let Ok(mut window) = window_q.get_single_mut() else { return };
+    set_cursor_grab(&mut window, false);
+}
+
+fn set_cursor_grab(window: &mut Window, grab: bool) {
+    let (grab_mode, visible) = if grab { (CursorGrabMode::Locked, false) } else { (CursorGrabMode::None, true) };
+    if window.cursor.grab_mode != grab_mode || window.cursor.visible != visible {
+        window.cursor.grab_mode = grab_mode;
+        window.cursor.visible = visible;
     }
 }
 
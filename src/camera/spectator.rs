@@ -0,0 +1,235 @@
+use bevy::{input::mouse::MouseMotion, prelude::*, window::PrimaryWindow};
+
+use crate::{
+    game::NetworkConfig,
+    input::action_map::{Action, ActionInput},
+    lobby::PeerRole,
+    player::{Player, PlayerStats},
+    ui::screen_open,
+    AppState,
+};
+
+use super::ThirdPersonCamera;
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Spectating>()
+            .add_systems(OnEnter(AppState::InGame), start_spectating_for_spectators)
+            .add_systems(OnExit(AppState::InGame), stop_spectating)
+            .add_systems(Update, (
+                toggle_spectator_mode,
+                (spectator_look, free_fly).chain().run_if(in_spectator_mode(SpectatorMode::Free)),
+                (cycle_follow_target, spectator_look, follow_target).chain().run_if(in_spectator_mode(SpectatorMode::Follow)),
+            ).chain().run_if(in_state(AppState::InGame)).run_if(not(screen_open)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpectatorMode {
+    /// the camera belongs to the ThirdPersonCamera systems
+    #[default]
+    Off,
+    Free,
+    Follow,
+}
+
+#[derive(Resource)]
+pub struct Spectating {
+    pub mode: SpectatorMode,
+    /// handle of the followed player
+    pub target: usize,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fly_speed: f32,
+    pub follow_distance: f32,
+}
+
+impl Default for Spectating {
+    fn default() -> Self {
+        Self {
+            mode: SpectatorMode::Off,
+            target: 0,
+            yaw: 0.0,
+            pitch: -0.3,
+            fly_speed: 8.0,
+            follow_distance: 4.0,
+        }
+    }
+}
+
+/// Marks the 3d camera while it is detached from the ThirdPersonCameraTarget.
+#[derive(Component)]
+pub struct SpectatorCamera;
+
+pub fn is_spectating(spectating: Res<Spectating>) -> bool {
+    spectating.mode != SpectatorMode::Off
+}
+
+fn in_spectator_mode(mode: SpectatorMode) -> impl Fn(Res<Spectating>) -> bool {
+    move |spectating: Res<Spectating>| spectating.mode == mode
+}
+
+fn start_spectating_for_spectators(
+    mut commands: Commands,
+    network: Res<NetworkConfig>,
+    mut spectating: ResMut<Spectating>,
+    cam_q: Query<(Entity, &Transform), With<ThirdPersonCamera>>,
+) {
+    if network.role != PeerRole::Spectator {
+        return;
+    }
+
+    spectating.mode = SpectatorMode::Follow;
+    for (e, transform) in cam_q.iter() {
+        commands.entity(e).insert(SpectatorCamera);
+        (spectating.yaw, spectating.pitch) = yaw_pitch(transform.rotation);
+    }
+}
+
+fn stop_spectating(mut commands: Commands, mut spectating: ResMut<Spectating>, cam_q: Query<Entity, With<SpectatorCamera>>) {
+    spectating.mode = SpectatorMode::Off;
+    for e in cam_q.iter() {
+        commands.entity(e).remove::<SpectatorCamera>();
+    }
+}
+
+fn yaw_pitch(rotation: Quat) -> (f32, f32) {
+    let (yaw, pitch, _) = rotation.to_euler(EulerRot::YXZ);
+    (yaw, pitch)
+}
+
+/// switches between third person (only for players), free flight and following other players
+fn toggle_spectator_mode(
+    mut commands: Commands,
+    actions: ActionInput,
+    network: Res<NetworkConfig>,
+    mut spectating: ResMut<Spectating>,
+    cam_q: Query<(Entity, &Transform), With<ThirdPersonCamera>>,
+) {
+    if !actions.just_pressed(Action::Spectate) {
+        return;
+    }
+
+    spectating.mode = match (spectating.mode, network.role) {
+        (SpectatorMode::Off, _) => SpectatorMode::Free,
+        (SpectatorMode::Free, _) => SpectatorMode::Follow,
+        (SpectatorMode::Follow, PeerRole::Player) => SpectatorMode::Off,
+        (SpectatorMode::Follow, PeerRole::Spectator) => SpectatorMode::Free,
+    };
+    info!("spectator mode: {:?}", spectating.mode);
+
+    for (e, transform) in cam_q.iter() {
+        if spectating.mode == SpectatorMode::Off {
+            commands.entity(e).remove::<SpectatorCamera>();
+        } else {
+            commands.entity(e).insert(SpectatorCamera);
+            (spectating.yaw, spectating.pitch) = yaw_pitch(transform.rotation);
+        }
+    }
+}
+
+fn spectator_look(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut mouse_evr: EventReader<MouseMotion>,
+    mut spectating: ResMut<Spectating>,
+    cam_q: Query<&ThirdPersonCamera, With<SpectatorCamera>>,
+) {
+    let mut rotation: Vec2 = mouse_evr.read().map(|ev| ev.delta).sum();
+    let Ok(cam) = cam_q.get_single() else { return };
+    let Ok(window) = window_q.get_single() else { return };
+
+    rotation *= cam.mouse_sensitivity;
+    if cam.invert_y {
+        rotation.y = -rotation.y;
+    }
+    spectating.yaw -= rotation.x / window.width() * std::f32::consts::PI;
+    spectating.pitch = (spectating.pitch - rotation.y / window.height() * std::f32::consts::PI)
+        .clamp(-1.54, 1.54);
+}
+
+fn free_fly(
+    time: Res<Time>,
+    actions: ActionInput,
+    spectating: Res<Spectating>,
+    mut cam_q: Query<&mut Transform, With<SpectatorCamera>>,
+) {
+    let Ok(mut transform) = cam_q.get_single_mut() else { return };
+
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, spectating.yaw, spectating.pitch, 0.0);
+
+    let mut direction = Vec3::ZERO;
+    if actions.pressed(Action::MoveForward) {
+        direction += transform.forward();
+    }
+    if actions.pressed(Action::MoveBack) {
+        direction += transform.back();
+    }
+    if actions.pressed(Action::MoveLeft) {
+        direction += transform.left();
+    }
+    if actions.pressed(Action::MoveRight) {
+        direction += transform.right();
+    }
+    if actions.pressed(Action::Jump) {
+        direction += Vec3::Y;
+    }
+    if actions.pressed(Action::Crouch) {
+        direction -= Vec3::Y;
+    }
+
+    let speed = if actions.pressed(Action::Run) {
+        spectating.fly_speed * 3.0
+    } else {
+        spectating.fly_speed
+    };
+
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_seconds();
+}
+
+/// only living players are followed, the camera moves on when the followed one dies
+fn cycle_follow_target(
+    actions: ActionInput,
+    mut spectating: ResMut<Spectating>,
+    player_q: Query<(&Player, &PlayerStats)>,
+) {
+    let mut handles: Vec<usize> = player_q
+        .iter()
+        .filter(|(_, stats)| stats.is_alive())
+        .map(|(p, _)| p.handle)
+        .collect();
+    if handles.is_empty() {
+        return;
+    }
+    handles.sort();
+
+    let current = handles.iter().position(|h| *h == spectating.target);
+    let next = if actions.just_pressed(Action::SpectateNext) {
+        current.map(|i| (i + 1) % handles.len()).unwrap_or(0)
+    } else if actions.just_pressed(Action::SpectatePrevious) {
+        current.map(|i| (i + handles.len() - 1) % handles.len()).unwrap_or(0)
+    } else {
+        // the followed player may have left or died
+        current.unwrap_or(0)
+    };
+
+    if handles[next] != spectating.target {
+        spectating.target = handles[next];
+        info!("following player {}", spectating.target);
+    }
+}
+
+fn follow_target(
+    spectating: Res<Spectating>,
+    player_q: Query<(&Player, &Transform), Without<SpectatorCamera>>,
+    mut cam_q: Query<(&ThirdPersonCamera, &mut Transform), With<SpectatorCamera>>,
+) {
+    let Ok((cam, mut cam_transform)) = cam_q.get_single_mut() else { return };
+    let Some((_, target)) = player_q.iter().find(|(p, _)| p.handle == spectating.target) else { return };
+
+    cam_transform.rotation = Quat::from_euler(EulerRot::YXZ, spectating.yaw, spectating.pitch, 0.0);
+    let focus = target.translation + cam.focus;
+    cam_transform.translation = focus + cam_transform.rotation * Vec3::new(0.0, 0.0, spectating.follow_distance);
+}
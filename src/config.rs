@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Loads a user config file (ron) by name, falls back to the default if it does not exist or can't be parsed.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let Some(content) = read(name) else {
+        return T::default();
+    };
+
+    match ron::from_str(&content) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("config {name} could not be parsed, using defaults: {e}");
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) {
+    let content = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("config {name} could not be serialized: {e}");
+            return;
+        }
+    };
+
+    write(name, &content);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn config_path(name: &str) -> Option<std::path::PathBuf> {
+    let dirs = directories::ProjectDirs::from("", "", "mi-game")?;
+    Some(dirs.config_dir().join(format!("{name}.ron")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(name: &str) -> Option<String> {
+    let path = config_path(name)?;
+    std::fs::read_to_string(path).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(name: &str, content: &str) {
+    let Some(path) = config_path(name) else {
+        warn!("no config directory found, {name} is not saved");
+        return;
+    };
+
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!("could not create config directory {}: {e}", dir.display());
+            return;
+        }
+    }
+
+    match std::fs::write(&path, content) {
+        Ok(_) => info!("saved config to {}", path.display()),
+        Err(e) => error!("could not write config {}: {e}", path.display()),
+    }
+}
+
+/// in the browser the configs are kept in the local storage of the page
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(name: &str) -> Option<String> {
+    local_storage()?.get_item(&format!("mi-game/{name}")).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(name: &str, content: &str) {
+    let Some(storage) = local_storage() else {
+        warn!("no local storage in this browser, {name} is not saved");
+        return;
+    };
+
+    match storage.set_item(&format!("mi-game/{name}"), content) {
+        Ok(_) => info!("saved config {name} to the local storage"),
+        Err(e) => error!("could not write config {name} to the local storage: {e:?}"),
+    }
+}
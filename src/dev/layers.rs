@@ -0,0 +1,223 @@
+//! Gizmos for the state of the gameplay systems, each layer is toggled on its own.
+//!
+//! `debug <layer>` in the console toggles a layer, F7 all of them. The game has no navigation
+//! mesh or AI yet, so there is no layer for paths.
+
+use bevy::{prelude::*, transform::TransformSystem, utils::HashSet};
+use bevy_rapier3d::prelude::*;
+use bevy_tnua::TnuaProximitySensor;
+
+use crate::{
+    camera::ThirdPersonCamera,
+    map::{
+        level::Level,
+        markers::{LevelTrigger, SceneSpawnPoints},
+        triggers::TriggerOccupants,
+        ActiveLevel,
+    },
+    player::{Foot, Player},
+    AppState,
+};
+
+pub struct DebugLayersPlugin;
+
+impl Plugin for DebugLayersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DebugLayers>()
+            .add_systems(Update, toggle_all_layers)
+            .add_systems(PostUpdate, (
+                draw_players.run_if(layer_shown(DebugLayer::Players)),
+                draw_feet.run_if(layer_shown(DebugLayer::Feet)),
+                draw_camera.run_if(layer_shown(DebugLayer::Camera)),
+                draw_spawn_points.run_if(layer_shown(DebugLayer::SpawnPoints)),
+                draw_triggers.run_if(layer_shown(DebugLayer::Triggers)),
+                draw_lights.run_if(layer_shown(DebugLayer::Lights)),
+            ).after(TransformSystem::TransformPropagate).run_if(in_state(AppState::InGame)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugLayer {
+    /// capsules and the ground sensor rays of tnua
+    Players,
+    /// red while the foot is on the ground and its step was played
+    Feet,
+    /// focus point and spring arm, the part which hits a wall is red
+    Camera,
+    SpawnPoints,
+    /// orange while something is inside
+    Triggers,
+    /// range of the point and spot lights
+    Lights,
+}
+
+impl DebugLayer {
+    pub const ALL: [DebugLayer; 6] = [
+        DebugLayer::Players,
+        DebugLayer::Feet,
+        DebugLayer::Camera,
+        DebugLayer::SpawnPoints,
+        DebugLayer::Triggers,
+        DebugLayer::Lights,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugLayer::Players => "players",
+            DebugLayer::Feet => "feet",
+            DebugLayer::Camera => "camera",
+            DebugLayer::SpawnPoints => "spawns",
+            DebugLayer::Triggers => "triggers",
+            DebugLayer::Lights => "lights",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layer| layer.name() == name)
+    }
+}
+
+/// the layers which are drawn
+#[derive(Resource, Default)]
+pub struct DebugLayers(pub HashSet<DebugLayer>);
+
+impl DebugLayers {
+    /// `true` if the layer is shown afterwards
+    pub fn toggle(&mut self, layer: DebugLayer) -> bool {
+        if !self.0.remove(&layer) {
+            self.0.insert(layer);
+        }
+        self.0.contains(&layer)
+    }
+
+    /// all layers, or none if any was shown
+    pub fn toggle_all(&mut self) -> bool {
+        if self.0.is_empty() {
+            self.0.extend(DebugLayer::ALL);
+            true
+        } else {
+            self.0.clear();
+            false
+        }
+    }
+}
+
+fn layer_shown(layer: DebugLayer) -> impl Fn(Res<DebugLayers>) -> bool {
+    move |layers: Res<DebugLayers>| layers.0.contains(&layer)
+}
+
+fn toggle_all_layers(keys: Res<Input<KeyCode>>, mut layers: ResMut<DebugLayers>) {
+    if keys.just_pressed(KeyCode::F7) {
+        layers.toggle_all();
+    }
+}
+
+fn draw_players(mut gizmos: Gizmos, player_q: Query<(&GlobalTransform, &Collider, Option<&TnuaProximitySensor>), With<Player>>) {
+    for (transform, collider, sensor) in player_q.iter() {
+        if let Some(capsule) = collider.raw.as_capsule() {
+            let (a, b) = (capsule.segment.a, capsule.segment.b);
+            let a = transform.transform_point(Vec3::new(a.x, a.y, a.z));
+            let b = transform.transform_point(Vec3::new(b.x, b.y, b.z));
+            let rotation = transform.compute_transform().rotation;
+            let radius = capsule.radius;
+
+            gizmos.sphere(a, rotation, radius, Color::CYAN);
+            gizmos.sphere(b, rotation, radius, Color::CYAN);
+            for side in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+                let side = rotation * side * radius;
+                gizmos.line(a + side, b + side, Color::CYAN);
+            }
+        }
+
+        let Some(sensor) = sensor else { continue };
+        let origin = transform.translation() + sensor.cast_origin;
+        let direction = sensor.cast_direction.normalize_or_zero();
+        match &sensor.output {
+            Some(output) => {
+                gizmos.line(origin, origin + direction * output.proximity, Color::GREEN);
+                gizmos.line(origin + direction * output.proximity, origin + direction * sensor.cast_range, Color::GRAY);
+            }
+            None => gizmos.line(origin, origin + direction * sensor.cast_range, Color::RED),
+        }
+    }
+}
+
+fn draw_feet(mut gizmos: Gizmos, foot_q: Query<(&GlobalTransform, &Foot)>) {
+    for (transform, foot) in foot_q.iter() {
+        let color = match (foot.triggered, foot.left) {
+            (true, _) => Color::RED,
+            (false, true) => Color::YELLOW,
+            (false, false) => Color::CYAN,
+        };
+        gizmos.sphere(transform.translation(), Quat::IDENTITY, 0.06, color);
+    }
+}
+
+/// the same arm as `sync_player_camera`, without the pull of the walls
+fn draw_camera(
+    mut gizmos: Gizmos,
+    player_q: Query<(&Transform, &Player)>,
+    camera_q: Query<(&ThirdPersonCamera, &Transform), Without<Player>>,
+) {
+    for (player_transform, player) in player_q.iter() {
+        let Some((cam, cam_t)) = player.camera.and_then(|camera| camera_q.get(camera).ok()) else { continue };
+
+        let focus = player_transform.translation + cam.focus;
+        let rotation = Mat3::from_quat(cam_t.rotation);
+        let offset = rotation.mul_vec3(Vec3::new(cam.offset.position.0, cam.offset.position.1, 0.0));
+        let arm = cam.focus + rotation.mul_vec3(Vec3::new(0.0, 0.0, cam.zoom.radius())) + offset;
+
+        gizmos.sphere(focus, Quat::IDENTITY, 0.05, Color::WHITE);
+        gizmos.line(focus, cam_t.translation, Color::GREEN);
+        gizmos.line(cam_t.translation, focus + arm, Color::RED);
+    }
+}
+
+fn draw_spawn_points(
+    mut gizmos: Gizmos,
+    active_level: Option<Res<ActiveLevel>>,
+    levels: Res<Assets<Level>>,
+    scene_spawn_points: Option<Res<SceneSpawnPoints>>,
+) {
+    let level = active_level.and_then(|active_level| levels.get(&active_level.0));
+    // the points of the scene win, see `move_players_to_scene_spawn_points`
+    let (points, color) = match (&scene_spawn_points, level) {
+        (Some(scene), _) => (scene.0.as_slice(), Color::GREEN),
+        (None, Some(level)) => (level.spawn_points.as_slice(), Color::LIME_GREEN),
+        (None, None) => return,
+    };
+    for point in points {
+        gizmos.circle(*point, Vec3::Y, 0.5, color);
+        gizmos.line(*point, *point + Vec3::Y * 2.0, color);
+    }
+}
+
+fn draw_triggers(mut gizmos: Gizmos, trigger_q: Query<(&GlobalTransform, &LevelTrigger, Option<&TriggerOccupants>)>) {
+    for (transform, trigger, occupants) in trigger_q.iter() {
+        let occupied = occupants.is_some_and(|occupants| !occupants.0.is_empty());
+        let color = if occupied { Color::ORANGE } else { Color::YELLOW };
+        let volume = transform.compute_matrix() * Mat4::from_scale(trigger.half_extents * 2.0);
+        gizmos.cuboid(Transform::from_matrix(volume), color);
+    }
+}
+
+fn draw_lights(
+    mut gizmos: Gizmos,
+    point_q: Query<(&GlobalTransform, &PointLight, &ViewVisibility)>,
+    spot_q: Query<(&GlobalTransform, &SpotLight, &ViewVisibility)>,
+) {
+    for (transform, light, visibility) in point_q.iter() {
+        let color = if visibility.get() { light.color } else { Color::DARK_GRAY };
+        gizmos.sphere(transform.translation(), Quat::IDENTITY, light.range, color);
+    }
+    for (transform, light, visibility) in spot_q.iter() {
+        let color = if visibility.get() { light.color } else { Color::DARK_GRAY };
+        let position = transform.translation();
+        let forward = transform.forward();
+        let end = position + forward * light.range;
+        let radius = light.range * light.outer_angle.tan();
+        gizmos.line(position, end, color);
+        gizmos.circle(end, forward, radius, color);
+    }
+}
@@ -0,0 +1,111 @@
+//! Tools for developing the game, only built with `--features dev`.
+//!
+//! Everything starts hidden and is toggled at runtime: F3 shows frame time and entity count,
+//! F4 the colliders of rapier, F5 the world inspector and F6 the bounding boxes of all meshes.
+//! F7 shows the gizmo layers of `layers`.
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_rapier3d::render::{DebugRenderContext, RapierDebugRenderPlugin};
+
+pub mod layers;
+
+pub struct DevToolsPlugin;
+
+impl Plugin for DevToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins((
+                FrameTimeDiagnosticsPlugin,
+                EntityCountDiagnosticsPlugin,
+                RapierDebugRenderPlugin::default().disabled(),
+                WorldInspectorPlugin::new().run_if(|tools: Res<DevTools>| tools.inspector),
+                layers::DebugLayersPlugin,
+            ))
+            .init_resource::<DevTools>()
+            .add_systems(Startup, spawn_stats_text)
+            .add_systems(Update, (
+                toggle_dev_tools,
+                apply_dev_tools.run_if(resource_changed::<DevTools>()),
+                update_stats_text,
+            ).chain());
+    }
+}
+
+/// which tools are shown, the console toggles the inspector as well
+#[derive(Resource, Default)]
+pub struct DevTools {
+    pub stats: bool,
+    pub physics: bool,
+    pub inspector: bool,
+    pub bounding_boxes: bool,
+}
+
+#[derive(Component)]
+struct StatsText;
+
+fn toggle_dev_tools(keys: Res<Input<KeyCode>>, mut tools: ResMut<DevTools>) {
+    if keys.just_pressed(KeyCode::F3) {
+        tools.stats = !tools.stats;
+    }
+    if keys.just_pressed(KeyCode::F4) {
+        tools.physics = !tools.physics;
+    }
+    if keys.just_pressed(KeyCode::F5) {
+        tools.inspector = !tools.inspector;
+    }
+    if keys.just_pressed(KeyCode::F6) {
+        tools.bounding_boxes = !tools.bounding_boxes;
+    }
+}
+
+fn apply_dev_tools(
+    tools: Res<DevTools>,
+    mut rapier_debug: ResMut<DebugRenderContext>,
+    mut gizmo_config: ResMut<GizmoConfig>,
+    mut stats_q: Query<&mut Visibility, With<StatsText>>,
+) {
+    rapier_debug.enabled = tools.physics;
+    gizmo_config.aabb.draw_all = tools.bounding_boxes;
+    for mut visibility in stats_q.iter_mut() {
+        *visibility = if tools.stats { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+/// the ui camera changes between menu and match, so the text is not bound to one
+fn spawn_stats_text(mut commands: Commands) {
+    let mut text = TextBundle::from_section(
+        "",
+        TextStyle {
+            font_size: 16.0,
+            color: Color::YELLOW,
+            ..default()
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(4.0),
+        right: Val::Px(6.0),
+        ..default()
+    });
+    text.visibility = Visibility::Hidden;
+    text.z_index = ZIndex::Global(30);
+    commands.spawn((text, StatsText));
+}
+
+fn update_stats_text(tools: Res<DevTools>, diagnostics: Res<DiagnosticsStore>, mut stats_q: Query<&mut Text, With<StatsText>>) {
+    if !tools.stats {
+        return;
+    }
+    let value = |id| diagnostics.get(id).and_then(|diagnostic| diagnostic.smoothed()).unwrap_or_default();
+    let fps = value(FrameTimeDiagnosticsPlugin::FPS);
+    let frame_time = value(FrameTimeDiagnosticsPlugin::FRAME_TIME);
+    let entities = value(EntityCountDiagnosticsPlugin::ENTITY_COUNT);
+
+    for mut text in stats_q.iter_mut() {
+        text.sections[0].value = format!("{fps:.0} fps  {frame_time:.1} ms  {entities:.0} entities");
+    }
+}
@@ -1,23 +1,22 @@
 use bevy::{
     prelude::*, 
-    core_pipeline::{tonemapping::Tonemapping, Skybox}, 
-    render::render_resource::{TextureViewDimension, TextureViewDescriptor}, 
-    asset::LoadState, 
+    core_pipeline::tonemapping::Tonemapping, 
     gltf::Gltf, 
     utils::HashMap, 
     input::mouse::MouseMotion, 
     core::{Pod, Zeroable}
 };
 
+use std::hash::{Hash, Hasher};
 use bevy_rapier3d::prelude::*;
 use bevy_tnua::controller::TnuaController;
-use iyes_progress::{prelude::AssetsLoading, Progress, ProgressSystem};
+use iyes_progress::{Progress, ProgressSystem};
 
 use bevy_ggrs::*;
 // the network thing
 use bevy_matchbox::prelude::*;
 
-use crate::{camera::{ThirdPersonCameraPlugin, ThirdPersonCamera}, player::{PlayerPlugin, Player, Head, self, MainPlayer}, AppState, Cubemap, map, despawn_screen, game, ui::splash::{splash_setup, OnSplashScreen, update_splash}};
+use crate::{map::{registry::MapRotation, time_of_day::Sun, AgreedMap, LevelEntity}, camera::spectator::{Spectating, SpectatorMode}, lobby::{Lobby, PeerRole, PlayerNames, update_lobby}, input::{gamepad::{ActiveGamepad, GamepadConfig, read_stick}, action_map::{Action, ActionInput}}, ui::{loading::TrackedLoading, OpenScreens}, camera::{ThirdPersonCameraPlugin, ThirdPersonCamera, PlayerCamera}, player::{PlayerPlugin, Player, Head, self, MainPlayer, interaction::LookTarget}, AppState, map, despawn_screen, game, settings::Settings};
 
 
 #[repr(C)]
@@ -26,75 +25,142 @@ pub struct PlayerState {
     pub head_rotation: Vec3,
     pub w: f32,
     pub input: u8,
-    _padding: [u8; 3],
+    /// quantized movement direction, x to the right and y forward, the length is the magnitude
+    pub move_x: i8,
+    pub move_y: i8,
+    /// index + 1 of the interactable the player looks at, 0 is nothing
+    pub target: u8,
+}
+
+impl PlayerState {
+    pub fn set_movement(&mut self, movement: Vec2) {
+        let movement = movement.clamp_length_max(1.0);
+        self.move_x = (movement.x * i8::MAX as f32).round() as i8;
+        self.move_y = (movement.y * i8::MAX as f32).round() as i8;
+    }
+
+    pub fn movement(&self) -> Vec2 {
+        let movement = Vec2::new(self.move_x as f32, self.move_y as f32) / i8::MAX as f32;
+        movement.clamp_length_max(1.0)
+    }
+
+    /// the rotation of the camera, sent with the input so the simulation doesn't read a local camera
+    pub fn set_look(&mut self, rotation: Quat) {
+        self.head_rotation = rotation.xyz();
+        self.w = rotation.w;
+    }
+
+    /// identity for inputs without a camera, the default state is all zero
+    pub fn look(&self) -> Quat {
+        let rotation = Quat::from_xyzw(self.head_rotation.x, self.head_rotation.y, self.head_rotation.z, self.w);
+        if rotation.length_squared() > 0.0 { rotation.normalize() } else { Quat::IDENTITY }
+    }
 }
 
 
 pub type Config = bevy_ggrs::GgrsConfig<PlayerState, PeerId>;
 
+/// Hash of the bits of the values for the checksums of ggrs, a synctest session compares them
+/// after every rollback.
+pub fn checksum_floats(floats: &[f32], ints: &[u32]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for float in floats {
+        float.to_bits().hash(&mut hasher);
+    }
+    ints.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn checksum_transform(transform: &Transform) -> u64 {
+    let (t, r) = (transform.translation, transform.rotation);
+    checksum_floats(&[t.x, t.y, t.z, r.x, r.y, r.z, r.w], &[])
+}
+
+/// ggrs runs the `GgrsSchedule` with its default of 60 frames per second
+pub const GGRS_FPS: usize = 60;
+
+/// Time of the match in ggrs frames. It is part of the rollback state, so every peer agrees on it.
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+pub struct MatchClock {
+    pub frame: u32,
+    pub round: u32,
+    /// frame in which the current round started
+    pub round_start: u32,
+}
+
+impl MatchClock {
+    pub fn seconds(&self) -> f32 {
+        self.frame as f32 / GGRS_FPS as f32
+    }
+}
+
 pub struct GamePlugin;
 
 #[derive(Resource)]
 pub struct GameResources {
-    pub map: Handle<Scene>,
     pub player: Handle<Gltf>,
     pub player_model: Handle<Scene>,
-    pub skybox: Handle<Image>,
     pub local_player_id: Option<PeerId>,
     //pub sound_foot_1: Handle<Source>,
 }
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        // the `SettingsPlugin` is added first, the fallback only matters if it isn't
+        let settings = app.world.get_resource::<Settings>().cloned().unwrap_or_else(Settings::load);
         app
-            //.add_plugins(WorldInspectorPlugin::new())
             .add_plugins(ThirdPersonCameraPlugin)
             .add_plugins(PlayerPlugin)
-            .add_plugins((
-                RapierPhysicsPlugin::<NoUserData>::default(),
-                //RapierDebugRenderPlugin::default(),
-            ))
+            .add_plugins(map::MapPlugin)
+            // the debug rendering is part of the dev tools
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
             .add_plugins(
                 GgrsPlugin::<Config>::default(),
             )
             .rollback_component_with_clone::<Transform>()
+            .checksum_component::<Transform>(checksum_transform)
+            .init_resource::<MatchClock>()
+            .rollback_resource_with_copy::<MatchClock>()
+            .checksum_resource::<MatchClock>(|clock| checksum_floats(&[], &[clock.frame, clock.round, clock.round_start]))
+            .add_systems(OnEnter(AppState::GameLoading), reset_match_clock)
+            .add_systems(GgrsSchedule, tick_match_clock.run_if(in_state(AppState::InGame)))
+            .insert_resource(NetworkConfig::from_args(&settings))
+            .add_systems(Update, apply_network_settings
+                .run_if(resource_changed::<Settings>().and_then(not(resource_added::<Settings>())))
+                .run_if(in_state(AppState::MainMenu)))
             //.add_systems(OnEnter(AppState::GameLoading), setup)
             .add_systems(
                 OnEnter(AppState::GameLoading), 
                 (
-                    splash_setup,
                     setup,
-                    map::setup, 
                     despawn_screen::<Camera2d>,
-                    start_matchbox_socket,
+                    start_matchbox_socket.run_if(is_online),
                 )
             )
-            .add_systems(OnExit(AppState::GameLoading), despawn_screen::<OnSplashScreen>)
-            .add_systems(Update, (update_splash).run_if(in_state(AppState::GameLoading)))
-            .add_systems(Update, skybox_asset_loaded.run_if(in_state(AppState::GameLoading)))
-            .add_systems(Update, (wait_for_players.track_progress()).run_if(in_state(AppState::GameLoading)))
-            .add_systems(ReadInputs, (read_local_inputs).run_if(in_state(AppState::InGame)));
+            .add_systems(OnEnter(AppState::MainMenu), (leave_game, restart_match.run_if(resource_exists::<RestartMatch>())).chain())
+            .add_systems(Update, (
+                wait_for_players.track_progress().after(update_lobby).run_if(is_online),
+                start_offline_session.track_progress().run_if(not(is_online)),
+            ).run_if(in_state(AppState::GameLoading)))
+            .add_systems(ReadInputs, (read_local_inputs).run_if(in_state(AppState::InGame)))
+            .add_systems(ReadInputs, read_idle_inputs.run_if(not(in_state(AppState::InGame))));
     }
 }
 
 pub fn load_game_assets(
     mut commands: Commands,
     ass: Res<AssetServer>,
-    mut loading: ResMut<AssetsLoading>,
+    mut loading: TrackedLoading,
 ) {
 
-    let map: Handle<Scene> = ass.load("de_dust2.glb#Scene0");
     let player: Handle<Gltf> = ass.load("my_character.glb");
     let player_model: Handle<Scene> = ass.load("my_character.glb#Scene0");
-    let skybox: Handle<Image> = ass.load("textures/Ryfjallet_cubemap.png");
 
-    loading.add(&map);
     loading.add(&player);
     loading.add(&player_model);
-    loading.add(&skybox);
     
     commands.insert_resource(GameResources {
-        map, player, player_model, skybox,
+        player, player_model,
         local_player_id: None,
     });
 
@@ -102,7 +168,6 @@ pub fn load_game_assets(
 
 fn setup(
     mut commands: Commands,
-    game_assets: Res<GameResources>,
 ) {
 
     println!("enter game state: loading");
@@ -114,7 +179,7 @@ fn setup(
         },
         transform: Transform::from_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_3)),
         ..default()
-    }, Name::new("GlobalLight")));
+    }, Name::new("GlobalLight"), Sun, LevelEntity));
 
     // camera
     commands.spawn((
@@ -128,108 +193,303 @@ fn setup(
             }),*/
             ..default()
         },
-        //camera::CameraController::default(),
-        //Skybox(skybox_handle.clone()),
+        // the skybox is added by `map::environment` when the sky of the level is loaded
         ThirdPersonCamera::default(),
     ));
 
-    commands.insert_resource(Cubemap {
-        is_loaded: false,
-        index: 0,
-        image_handle: game_assets.skybox.clone(),
-    });
-
 }
 
-fn skybox_asset_loaded(
-    asset_server: Res<AssetServer>,
-    mut images: ResMut<Assets<Image>>,
-    mut cubemap: ResMut<Cubemap>,
-    mut skyboxes: Query<&mut Skybox>,
+/// Back in the menu nothing of a match is left, after it ended or when loading failed.
+/// The level itself is removed by the map plugin.
+fn leave_game(
+    mut commands: Commands,
+    mut game_resources: ResMut<GameResources>,
+    mut time: ResMut<Time<Virtual>>,
+    match_q: Query<Entity, Or<(With<Player>, With<PlayerCamera>, With<ThirdPersonCamera>)>>,
+    ui_camera_q: Query<(), With<Camera2d>>,
 ) {
-    if !cubemap.is_loaded && asset_server.load_state(&cubemap.image_handle) == LoadState::Loaded {
-        let image = images.get_mut(&cubemap.image_handle).unwrap();
-        // NOTE: PNGs do not have any metadata that could indicate they contain a cubemap texture,
-        // so they appear as one texture. The following code reconfigures the texture as necessary.
-        if image.texture_descriptor.array_layer_count() == 1 {
-            image.reinterpret_stacked_2d_as_array(image.height() / image.width());
-            image.texture_view_descriptor = Some(TextureViewDescriptor {
-                dimension: Some(TextureViewDimension::Cube),
-                ..default()
-            });
-        }
+    commands.remove_resource::<bevy_ggrs::Session<Config>>();
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+    game_resources.local_player_id = None;
+    // the console can change it in an offline match
+    time.set_relative_speed(1.0);
+
+    for e in match_q.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    // loading the match removed the camera of the menu
+    if ui_camera_q.is_empty() {
+        commands.spawn(Camera2dBundle::default());
+    }
+}
+
+/// Inserted by the console to load the next map of the rotation right after leaving the match.
+#[derive(Resource)]
+pub struct RestartMatch;
+
+fn restart_match(mut commands: Commands, mut app_state: ResMut<NextState<AppState>>) {
+    commands.remove_resource::<RestartMatch>();
+    app_state.set(AppState::GameLoading);
+}
 
-        for mut skybox in &mut skyboxes {
-            skybox.0 = cubemap.image_handle.clone();
+/// How to connect to the other peers, is filled from the settings and the command line.
+#[derive(Resource, Clone)]
+pub struct NetworkConfig {
+    pub server: String,
+    pub room: String,
+    pub num_players: usize,
+    /// how many spectators are expected in the room, every peer has to agree on this
+    pub num_spectators: usize,
+    pub role: PeerRole,
+    pub input_delay: usize,
+    /// the display name which is sent to the other peers
+    pub name: String,
+    /// no socket, all players are local in a synctest session
+    pub offline: bool,
+}
+
+impl NetworkConfig {
+    /// the settings give the defaults, the command line overrides them
+    pub fn from_args(settings: &Settings) -> Self {
+        let mut config = Self {
+            server: settings.server.clone(),
+            room: "hitomowaji".to_string(),
+            num_players: 2,
+            num_spectators: 0,
+            role: PeerRole::Player,
+            input_delay: settings.input_delay,
+            name: settings.name.clone(),
+            offline: false,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--spectate" => config.role = PeerRole::Spectator,
+                "--offline" => config.offline = true,
+                "--spectators" => {
+                    config.num_spectators = args.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                }
+                "--name" => {
+                    if let Some(name) = args.next() {
+                        config.name = name;
+                    }
+                }
+                "--room" => {
+                    if let Some(room) = args.next() {
+                        config.room = room;
+                    }
+                }
+                "--server" => {
+                    if let Some(server) = args.next() {
+                        config.server = server;
+                    }
+                }
+                _ => {}
+            }
         }
+        config
+    }
 
-        cubemap.is_loaded = true;
+    pub fn room_url(&self) -> String {
+        format!("{}/{}?next={}", self.server, self.room, self.num_players + self.num_spectators)
     }
 }
 
+/// a name or input delay which is changed in the settings is used for the next match
+fn apply_network_settings(settings: Res<Settings>, mut network: ResMut<NetworkConfig>) {
+    network.name = settings.name.clone();
+    network.server = settings.server.clone();
+    network.input_delay = settings.input_delay;
+}
 
-fn start_matchbox_socket(mut commands: Commands) {
-    let room_url = "ws://10.42.200.56:3536/hitomowaji?next=2";
-    info!("connecting to {room_url}");
-    commands.insert_resource(MatchboxSocket::new_ggrs(room_url));
+pub fn is_online(network: Res<NetworkConfig>) -> bool {
+    !network.offline
+}
+
+fn start_matchbox_socket(mut commands: Commands, network: Res<NetworkConfig>) {
+    let room_url = network.room_url();
+    info!("connecting to {room_url} as {:?}", network.role);
+
+    let socket = WebRtcSocketBuilder::new(room_url)
+        .add_channel(ChannelConfig::ggrs())
+        .add_channel(ChannelConfig::reliable());
+    commands.insert_resource(MatchboxSocket::from(socket));
 }
 
 fn wait_for_players(
     mut commands: Commands,
-    mut socket: ResMut<MatchboxSocket<SingleChannel>>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
     mut game_resources: ResMut<GameResources>,
+    network: Res<NetworkConfig>,
+    rotation: Res<MapRotation>,
+    agreed_map: Option<Res<AgreedMap>>,
+    session: Option<Res<bevy_ggrs::Session<Config>>>,
+    lobby: Res<Lobby>,
 ) -> Progress {
+    // the session is already running, we only wait for the other loading steps
+    if session.is_some() {
+        return true.into();
+    }
     if socket.get_channel(0).is_err() {
         return false.into();
     }
+    let Some(local_id) = socket.id() else {
+        return false.into();
+    };
 
-    socket.update_peers();
-    let players = socket.players();
+    let num_players = network.num_players;
+    let players = lobby.sorted_players(local_id, network.role);
+    let spectators = lobby.sorted_spectators();
 
-    let num_players = 2;
-    if players.len() < num_players {
+    let num_spectators = if network.role == PeerRole::Spectator {
+        spectators.len() + 1
+    } else {
+        spectators.len()
+    };
+
+    if !lobby.all_greeted() || players.len() < num_players || num_spectators < network.num_spectators {
         return false.into();
     }
 
     info!("all peers have joined, going in-game");
 
-    let mut session_builder = ggrs::SessionBuilder::<Config>::new()
+    // the first player is the host and decides the map, everybody sent its offer with the hello
+    if agreed_map.is_none() {
+        let host = players[0];
+        let offer = if host == local_id {
+            rotation.offer(local_id)
+        } else {
+            lobby.offers[&host].clone()
+        };
+        commands.insert_resource(AgreedMap(offer));
+    }
+
+    let session_builder = ggrs::SessionBuilder::<Config>::new()
         .with_num_players(num_players)
-        .with_input_delay(2);
+        .with_input_delay(network.input_delay);
+
+    let channel = socket.take_channel(0).unwrap();
+
+    if network.role == PeerRole::Spectator {
+        // the first player is the host for all spectators
+        let ggrs_session = session_builder.start_spectator_session(players[0], channel);
+        commands.insert_resource(bevy_ggrs::Session::Spectator(ggrs_session));
+    } else {
+        let mut session_builder = session_builder;
+        for (i, player) in players.iter().enumerate() {
+            let player_type = if *player == local_id {
+                ggrs::PlayerType::Local
+            } else {
+                ggrs::PlayerType::Remote(*player)
+            };
+            session_builder = session_builder
+                .add_player(player_type, i)
+                .expect("failed to add player");
+        }
+
+        if players[0] == local_id {
+            for (i, spectator) in spectators.into_iter().enumerate() {
+                session_builder = session_builder
+                    .add_player(ggrs::PlayerType::Spectator(spectator), num_players + i)
+                    .expect("failed to add spectator");
+            }
+        }
+
+        let ggrs_session = session_builder
+            .start_p2p_session(channel)
+            .expect("failed to start session");
+
+        commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
+    }
 
-    for (i, player) in players.into_iter().enumerate() {
+    // peers which didn't say their name get the default of `PlayerNames`
+    let names = players
+        .iter()
+        .enumerate()
+        .map(|(i, player)| match lobby.names.get(player) {
+            _ if *player == local_id => network.name.clone(),
+            Some(name) => name.clone(),
+            None => format!("Player {}", i + 1),
+        })
+        .collect();
+    commands.insert_resource(PlayerNames(names));
+
+    game_resources.local_player_id = Some(local_id);
+    return true.into();
+}
+
+/// All players are on this machine. The checksums of the transforms, the player stats and the match
+/// clock are registered, but the check distance is 0: rapier steps in `PostUpdate`, outside the
+/// `GgrsSchedule`, so resimulated frames would have no physics and only report false desyncs.
+fn start_offline_session(
+    mut commands: Commands,
+    network: Res<NetworkConfig>,
+    rotation: Res<MapRotation>,
+    time: Res<Time>,
+    agreed_map: Option<Res<AgreedMap>>,
+    session: Option<Res<bevy_ggrs::Session<Config>>>,
+) -> Progress {
+    if session.is_some() {
+        return true.into();
+    }
+
+    if agreed_map.is_none() {
+        commands.insert_resource(AgreedMap(rotation.offer_with_seed(time.elapsed().as_nanos() as u64)));
+    }
+
+    let mut session_builder = ggrs::SessionBuilder::<Config>::new()
+        .with_num_players(network.num_players)
+        .with_check_distance(0)
+        .with_input_delay(network.input_delay);
+    for i in 0..network.num_players {
         session_builder = session_builder
-            .add_player(player, i)
+            .add_player(ggrs::PlayerType::Local, i)
             .expect("failed to add player");
     }
+    let ggrs_session = session_builder
+        .start_synctest_session()
+        .expect("failed to start synctest session");
 
-    let channel = socket.take_channel(0).unwrap();
+    let names = (0..network.num_players)
+        .map(|i| if i == 0 { network.name.clone() } else { format!("Player {}", i + 1) })
+        .collect();
+    commands.insert_resource(PlayerNames(names));
 
-    let ggrs_session = session_builder
-        .start_p2p_session(channel)
-        .expect("failed to start session");
+    info!("playing offline");
+    commands.insert_resource(bevy_ggrs::Session::SyncTest(ggrs_session));
+    true.into()
+}
 
-    commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
 
-    game_resources.local_player_id = Some(socket.id().unwrap());
-    return true.into();
+fn reset_match_clock(mut clock: ResMut<MatchClock>) {
+    *clock = MatchClock::default();
 }
 
+pub fn tick_match_clock(mut clock: ResMut<MatchClock>) {
+    clock.frame += 1;
+}
 
-pub const INPUT_UP: u8 = 1 << 0;
-pub const INPUT_DOWN: u8 = 1 << 1;
-pub const INPUT_LEFT: u8 = 1 << 2;
-pub const INPUT_RIGHT: u8 = 1 << 3;
-pub const INPUT_JUMP: u8 = 1 << 4;
-pub const INPUT_RUN: u8 = 1 << 5;
+pub const INPUT_JUMP: u8 = 1 << 0;
+pub const INPUT_RUN: u8 = 1 << 1;
+pub const INPUT_CROUCH: u8 = 1 << 2;
+pub const INPUT_DASH: u8 = 1 << 3;
+pub const INPUT_INTERACT: u8 = 1 << 4;
 
 fn read_local_inputs(
     mut commands: Commands,
-    keys: Res<Input<KeyCode>>,
+    actions: ActionInput,
+    screens: OpenScreens,
+    spectating: Res<Spectating>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    active_gamepad: Res<ActiveGamepad>,
+    gamepad_config: Res<GamepadConfig>,
     local_players: Res<LocalPlayers>,
+    look_target: Res<LookTarget>,
+    network: Res<NetworkConfig>,
 
     player_query: Query<&Player, With<MainPlayer>>,
     head_query: Query<&Transform, With<Head>>,
+    camera_query: Query<&Transform, With<PlayerCamera>>,
 ) {
     
     
@@ -238,28 +498,67 @@ fn read_local_inputs(
     let mut local_inputs = HashMap::new();
     
     for handle in &local_players.0 {
+        // offline every player is local, the others just stand around
+        if network.offline && *handle != local_players.0[0] {
+            local_inputs.insert(*handle, PlayerState::default());
+            continue;
+        }
 
 
         let mut input = 0u8;
 
-        if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
-            input |= INPUT_UP;
+        let mut movement = Vec2::ZERO;
+
+        if actions.pressed(Action::MoveForward) {
+            movement.y += 1.0;
         }
-        if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
-            input |= INPUT_DOWN;
+        if actions.pressed(Action::MoveBack) {
+            movement.y -= 1.0;
         }
-        if keys.any_pressed([KeyCode::Left, KeyCode::A]) {
-            input |= INPUT_LEFT
+        if actions.pressed(Action::MoveLeft) {
+            movement.x -= 1.0;
         }
-        if keys.any_pressed([KeyCode::Right, KeyCode::D]) {
-            input |= INPUT_RIGHT;
+        if actions.pressed(Action::MoveRight) {
+            movement.x += 1.0;
         }
-        if keys.any_pressed([KeyCode::Space, KeyCode::Return]) {
+        movement = movement.normalize_or_zero();
+
+        if actions.pressed(Action::Jump) {
             input |= INPUT_JUMP;
         }
-        if keys.any_pressed([KeyCode::ShiftLeft]) {
+        if actions.pressed(Action::Run) {
             input |= INPUT_RUN;
         }
+        if actions.pressed(Action::Crouch) {
+            input |= INPUT_CROUCH;
+        }
+        if actions.pressed(Action::Dash) {
+            input |= INPUT_DASH;
+        }
+        if actions.pressed(Action::Interact) {
+            input |= INPUT_INTERACT;
+        }
+
+        if let Some(gamepad) = active_gamepad.gamepad {
+            let stick = read_stick(
+                &gamepad_axes,
+                gamepad,
+                GamepadAxisType::LeftStickX,
+                GamepadAxisType::LeftStickY,
+                gamepad_config.move_deadzone,
+            );
+
+            // the stick wins over the keys, so it is possible to walk slowly
+            if stick != Vec2::ZERO {
+                movement = stick;
+            }
+        }
+
+        // a detached camera uses the movement keys itself, a screen over the game takes the input
+        if screens.any() || spectating.mode != SpectatorMode::Off {
+            input = 0;
+            movement = Vec2::ZERO;
+        }
 
         
         //let rotation = if let Ok(local_player) = player_query.get_single() {
@@ -283,15 +582,27 @@ fn read_local_inputs(
         //    );
 
         //} else {
-            local_inputs.insert(*handle, 
-                PlayerState {
-                    input,
-                    ..default()
-                }    
-            );
+            let mut state = PlayerState {
+                input,
+                target: look_target.index.map_or(0, |index| index + 1),
+                ..default()
+            };
+            state.set_movement(movement);
+            let camera = player_query.get_single().ok().and_then(|player| player.camera);
+            if let Some(camera) = camera.and_then(|camera| camera_query.get(camera).ok()) {
+                state.set_look(camera.rotation);
+            }
+            local_inputs.insert(*handle, state);
         //}
 
     }
 
+    commands.insert_resource(LocalInputs::<Config>(local_inputs));
+}
+
+/// The session already runs while the level is loading and ggrs needs an input for every frame,
+/// the players stand still until the match is in game.
+fn read_idle_inputs(mut commands: Commands, local_players: Res<LocalPlayers>) {
+    let local_inputs = local_players.0.iter().map(|handle| (*handle, PlayerState::default())).collect();
     commands.insert_resource(LocalInputs::<Config>(local_inputs));
 }
\ No newline at end of file
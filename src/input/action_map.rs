@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+use super::gamepad::ActiveGamepad;
+
+pub const BINDINGS_CONFIG: &str = "bindings";
+
+/// every action can have up to this many bindings
+pub const BINDING_SLOTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Run,
+    Crouch,
+    Dash,
+    Interact,
+    /// the in-game menu
+    Menu,
+    OpenControls,
+    Spectate,
+    SpectateNext,
+    SpectatePrevious,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Run,
+        Action::Crouch,
+        Action::Dash,
+        Action::Interact,
+        Action::Menu,
+        Action::OpenControls,
+        Action::Spectate,
+        Action::SpectateNext,
+        Action::SpectatePrevious,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBack => "Move back",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Jump => "Jump",
+            Action::Run => "Run",
+            Action::Crouch => "Crouch",
+            Action::Dash => "Dash",
+            Action::Interact => "Use",
+            Action::Menu => "Menu",
+            Action::OpenControls => "Controls",
+            Action::Spectate => "Spectator camera",
+            Action::SpectateNext => "Next player",
+            Action::SpectatePrevious => "Previous player",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::Gamepad(button) => format!("Pad {button:?}"),
+        }
+    }
+}
+
+/// Maps named actions to keys, mouse buttons and gamepad buttons.
+/// It is loaded from the user config on startup and written back by the rebinding screen.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use Binding::*;
+
+        let bindings = BTreeMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::W), Key(KeyCode::Up)]),
+            (Action::MoveBack, vec![Key(KeyCode::S), Key(KeyCode::Down)]),
+            (Action::MoveLeft, vec![Key(KeyCode::A), Key(KeyCode::Left)]),
+            (Action::MoveRight, vec![Key(KeyCode::D), Key(KeyCode::Right)]),
+            (Action::Jump, vec![Key(KeyCode::Space), Key(KeyCode::Return), Gamepad(GamepadButtonType::South)]),
+            (Action::Run, vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButtonType::LeftTrigger2), Gamepad(GamepadButtonType::LeftThumb)]),
+            (Action::Crouch, vec![Key(KeyCode::ControlLeft), Key(KeyCode::C), Gamepad(GamepadButtonType::East)]),
+            (Action::Dash, vec![Key(KeyCode::Q), Gamepad(GamepadButtonType::West)]),
+            (Action::Interact, vec![Key(KeyCode::E), Gamepad(GamepadButtonType::North)]),
+            (Action::Menu, vec![Key(KeyCode::Escape), Gamepad(GamepadButtonType::Start)]),
+            (Action::OpenControls, vec![Key(KeyCode::F1)]),
+            (Action::Spectate, vec![Key(KeyCode::F)]),
+            (Action::SpectateNext, vec![Mouse(MouseButton::Left), Gamepad(GamepadButtonType::RightTrigger)]),
+            (Action::SpectatePrevious, vec![Mouse(MouseButton::Right), Gamepad(GamepadButtonType::LeftTrigger)]),
+        ]);
+
+        Self { bindings }
+    }
+}
+
+impl ActionMap {
+    pub fn load() -> Self {
+        let mut map: Self = config::load(BINDINGS_CONFIG);
+        // actions which are newer than the saved config get their default bindings
+        for (action, bindings) in Self::default().bindings {
+            map.bindings.entry(action).or_insert(bindings);
+        }
+        map
+    }
+
+    pub fn save(&self) {
+        config::save(BINDINGS_CONFIG, self);
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Sets the binding of an action slot. An empty slot at the end of the list appends the binding.
+    pub fn bind(&mut self, action: Action, slot: usize, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        // the same binding twice on one action is useless, an existing one changes places instead
+        match bindings.iter().position(|b| *b == binding) {
+            Some(existing) if slot < bindings.len() => bindings.swap(existing, slot),
+            Some(existing) => {
+                bindings.remove(existing);
+                bindings.push(binding);
+            }
+            None if slot < bindings.len() => bindings[slot] = binding,
+            None if bindings.len() < BINDING_SLOTS => bindings.push(binding),
+            None => {}
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, slot: usize) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            if slot < bindings.len() {
+                bindings.remove(slot);
+            }
+        }
+    }
+
+    /// All other actions which share the binding with the given action.
+    pub fn conflicts(&self, action: Action, binding: Binding) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(other, bindings)| **other != action && bindings.contains(&binding))
+            .map(|(other, _)| *other)
+            .collect()
+    }
+
+    pub fn has_conflicts(&self, action: Action) -> bool {
+        self.get(action).iter().any(|binding| !self.conflicts(action, *binding).is_empty())
+    }
+}
+
+/// Every input reading gameplay system should ask this param instead of reading KeyCodes directly.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    map: Res<'w, ActionMap>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    active_gamepad: Res<'w, ActiveGamepad>,
+}
+
+impl<'w> ActionInput<'w> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.map.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keys.pressed(*key),
+            Binding::Mouse(button) => self.mouse.pressed(*button),
+            Binding::Gamepad(button_type) => self
+                .active_gamepad
+                .gamepad
+                .is_some_and(|gamepad| self.gamepad_buttons.pressed(GamepadButton::new(gamepad, *button_type))),
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.map.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keys.just_pressed(*key),
+            Binding::Mouse(button) => self.mouse.just_pressed(*button),
+            Binding::Gamepad(button_type) => self
+                .active_gamepad
+                .gamepad
+                .is_some_and(|gamepad| self.gamepad_buttons.just_pressed(GamepadButton::new(gamepad, *button_type))),
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::{
+    input::gamepad::{GamepadConnection, GamepadConnectionEvent, GamepadButtonChangedEvent},
+    prelude::*,
+};
+
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<GamepadConfig>()
+            .add_systems(PreUpdate, track_active_gamepad);
+    }
+}
+
+/// The gamepad which is currently used by the local player.
+/// The last gamepad on which a button was pressed becomes the active one.
+#[derive(Resource, Default)]
+pub struct ActiveGamepad {
+    pub gamepad: Option<Gamepad>,
+    pub name: Option<String>,
+}
+
+#[derive(Resource)]
+pub struct GamepadConfig {
+    /// radial deadzone for the movement stick
+    pub move_deadzone: f32,
+    /// radial deadzone for the camera stick
+    pub look_deadzone: f32,
+    /// rotation speed of the camera in radians per second at full stick deflection
+    pub look_speed: Vec2,
+    /// time in seconds until the camera reaches the full look_speed
+    pub look_acceleration_time: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            move_deadzone: 0.2,
+            look_deadzone: 0.15,
+            look_speed: Vec2::new(3.5, 2.0),
+            look_acceleration_time: 0.35,
+        }
+    }
+}
+
+fn track_active_gamepad(
+    mut active: ResMut<ActiveGamepad>,
+    gamepads: Res<Gamepads>,
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    mut button_events: EventReader<GamepadButtonChangedEvent>,
+) {
+    for ev in connection_events.read() {
+        match &ev.connection {
+            GamepadConnection::Connected(info) => {
+                info!("gamepad connected: {} ({:?})", info.name, ev.gamepad);
+                if active.gamepad.is_none() {
+                    active.gamepad = Some(ev.gamepad);
+                    active.name = Some(info.name.clone());
+                }
+            }
+            GamepadConnection::Disconnected => {
+                info!("gamepad disconnected: {:?}", ev.gamepad);
+                if active.gamepad == Some(ev.gamepad) {
+                    // fall back to any other connected gamepad
+                    active.gamepad = gamepads.iter().find(|g| *g != ev.gamepad);
+                    active.name = active.gamepad.and_then(|g| gamepads.name(g)).map(String::from);
+                }
+            }
+        }
+    }
+
+    for ev in button_events.read() {
+        if ev.value > 0.5 && active.gamepad != Some(ev.gamepad) && gamepads.contains(ev.gamepad) {
+            active.gamepad = Some(ev.gamepad);
+            active.name = gamepads.name(ev.gamepad).map(String::from);
+        }
+    }
+}
+
+/// Reads a stick of the given gamepad and applies a radial deadzone.
+/// The result is rescaled, so the length starts at 0.0 right after the deadzone and is at most 1.0.
+pub fn read_stick(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+    deadzone: f32,
+) -> Vec2 {
+    let stick = Vec2::new(
+        axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+        axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+    );
+
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick / length * scaled
+}
@@ -0,0 +1,14 @@
+use bevy::prelude::*;
+
+pub mod action_map;
+pub mod gamepad;
+
+pub struct GameInputPlugin;
+
+impl Plugin for GameInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(action_map::ActionMap::load())
+            .add_plugins(gamepad::GamepadPlugin);
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_matchbox::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{map::{generator::SplitMix64, registry::{MapOffer, MapRotation}}, AppState};
+
+/// channel 0 is used by ggrs, this one is reliable and used to agree on things before the session starts
+pub const LOBBY_CHANNEL: usize = 1;
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Lobby>()
+            .init_resource::<PlayerNames>()
+            .add_systems(OnEnter(AppState::GameLoading), reset_lobby)
+            .add_systems(Update, update_lobby
+                .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>())
+                .run_if(in_state(AppState::GameLoading)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerRole {
+    Player,
+    Spectator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LobbyMessage {
+    /// the map offer is used if this peer turns out to be the host
+    Hello { role: PeerRole, offer: MapOffer, name: String },
+}
+
+/// Everything we know about the other peers before the ggrs session starts.
+#[derive(Resource, Default)]
+pub struct Lobby {
+    pub roles: HashMap<PeerId, PeerRole>,
+    pub offers: HashMap<PeerId, MapOffer>,
+    /// the display names, shown above the players
+    pub names: HashMap<PeerId, String>,
+    pub connected: Vec<PeerId>,
+}
+
+impl Lobby {
+    /// true if every connected peer told us who it is
+    pub fn all_greeted(&self) -> bool {
+        self.connected.iter().all(|peer| self.roles.contains_key(peer))
+    }
+
+    /// all players (local one included) sorted by their id, so every peer gets the same order
+    pub fn sorted_players(&self, local: PeerId, local_role: PeerRole) -> Vec<PeerId> {
+        let mut players: Vec<PeerId> = self
+            .connected
+            .iter()
+            .filter(|peer| self.roles.get(*peer) == Some(&PeerRole::Player))
+            .copied()
+            .collect();
+        if local_role == PeerRole::Player {
+            players.push(local);
+        }
+        players.sort();
+        players
+    }
+
+    pub fn sorted_spectators(&self) -> Vec<PeerId> {
+        let mut spectators: Vec<PeerId> = self
+            .connected
+            .iter()
+            .filter(|peer| self.roles.get(*peer) == Some(&PeerRole::Spectator))
+            .copied()
+            .collect();
+        spectators.sort();
+        spectators
+    }
+}
+
+/// The display names of the players by their ggrs handle, filled when the session starts.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct PlayerNames(pub Vec<String>);
+
+impl PlayerNames {
+    pub fn get(&self, handle: usize) -> String {
+        self.0.get(handle).cloned().unwrap_or_else(|| format!("Player {}", handle + 1))
+    }
+}
+
+pub fn send_lobby_message(socket: &mut MatchboxSocket<MultipleChannels>, peer: PeerId, message: &LobbyMessage) {
+    let Ok(text) = ron::to_string(message) else {
+        error!("could not serialize lobby message {message:?}");
+        return;
+    };
+    let Ok(channel) = socket.get_channel(LOBBY_CHANNEL) else { return };
+    channel.send(text.into_bytes().into_boxed_slice(), peer);
+}
+
+/// a room code which is easy to read out loud, like `hitomowaji`
+pub fn room_code(seed: u64) -> String {
+    const CONSONANTS: &[u8] = b"bdfghjkmnprstwz";
+    const VOWELS: &[u8] = b"aeiou";
+
+    let mut rng = SplitMix64::new(seed);
+    (0..5)
+        .flat_map(|_| {
+            let consonant = CONSONANTS[rng.below(CONSONANTS.len() as u32) as usize];
+            let vowel = VOWELS[rng.below(VOWELS.len() as u32) as usize];
+            [consonant as char, vowel as char]
+        })
+        .collect()
+}
+
+fn reset_lobby(mut lobby: ResMut<Lobby>) {
+    *lobby = Lobby::default();
+}
+
+pub fn update_lobby(
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut lobby: ResMut<Lobby>,
+    network: Res<crate::game::NetworkConfig>,
+    rotation: Res<MapRotation>,
+) {
+    if socket.get_channel(LOBBY_CHANNEL).is_err() {
+        return;
+    }
+    let Some(local_id) = socket.id() else { return };
+    let hello = LobbyMessage::Hello {
+        role: network.role,
+        offer: rotation.offer(local_id),
+        name: network.name.clone(),
+    };
+
+    for (peer, state) in socket.update_peers() {
+        match state {
+            PeerState::Connected => {
+                info!("peer {peer} connected");
+                lobby.connected.push(peer);
+                send_lobby_message(&mut socket, peer, &hello);
+            }
+            PeerState::Disconnected => {
+                info!("peer {peer} disconnected");
+                lobby.connected.retain(|p| *p != peer);
+                lobby.roles.remove(&peer);
+                lobby.offers.remove(&peer);
+                lobby.names.remove(&peer);
+            }
+        }
+    }
+
+    let Ok(channel) = socket.get_channel(LOBBY_CHANNEL) else { return };
+    for (peer, packet) in channel.receive() {
+        let message = std::str::from_utf8(&packet)
+            .ok()
+            .and_then(|text| ron::from_str::<LobbyMessage>(text).ok());
+
+        match message {
+            Some(LobbyMessage::Hello { role, offer, name }) => {
+                info!("peer {peer} ({name}) joins as {role:?} and wants to play {}", offer.map);
+                lobby.roles.insert(peer, role);
+                lobby.offers.insert(peer, offer);
+                lobby.names.insert(peer, name);
+            }
+            None => warn!("invalid lobby message from {peer}"),
+        }
+    }
+}
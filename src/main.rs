@@ -1,8 +1,7 @@
 
 use bevy::prelude::*;
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use iyes_progress::prelude::*;
-use ui::splash;
+use ui::loading;
 
 
 mod game;
@@ -10,13 +9,28 @@ mod map;
 mod camera;
 mod player;
 mod ui;
+mod input;
+mod config;
+mod settings;
+mod lobby;
+#[cfg(feature = "dev")]
+mod dev;
 
 
 #[derive(Resource)]
 struct Cubemap {
     is_loaded: bool,
+    /// the image which is shown, the day sky is the first and the night sky the second
     index: usize,
-    image_handle: Handle<Image>,
+    images: Vec<Handle<Image>>,
+    /// prefiltered diffuse and specular maps, without them only the ambient light is used
+    environment_map: Option<(Handle<Image>, Handle<Image>)>,
+}
+
+impl Cubemap {
+    fn image(&self) -> Handle<Image> {
+        self.images[self.index.min(self.images.len() - 1)].clone()
+    }
 }
 
 #[derive(Component)]
@@ -26,13 +40,14 @@ pub struct MainCamera;
 enum AppState {
     #[default]
     Splash,
-    //MainMenu,
+    MainMenu,
     GameLoading,
     InGame,
 }
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(
             WindowPlugin {
                 primary_window: Some(Window {
@@ -42,11 +57,10 @@ fn main() {
                 ..default()
             }
         ))
-        .add_plugins(WorldInspectorPlugin::new())
         .add_state::<AppState>()
         .add_plugins(
             ProgressPlugin::new(AppState::Splash)
-                .continue_to(AppState::GameLoading)
+                .continue_to(AppState::MainMenu)
                 .track_assets(),
         )
         .add_plugins(
@@ -54,17 +68,24 @@ fn main() {
                 .continue_to(AppState::InGame)
                 .track_assets()
         )
-        .add_plugins(splash::SplashPlugin)
+        .add_plugins(settings::SettingsPlugin)
+        .add_plugins(input::GameInputPlugin)
+        .add_plugins(loading::LoadingScreenPlugin)
+        .add_plugins(ui::menu::MainMenuPlugin)
+        .add_plugins(ui::gamepad::GamepadStatusPlugin)
+        .add_plugins(ui::rebind::RebindPlugin)
+        .add_plugins(ui::settings::SettingsScreenPlugin)
+        .add_plugins(ui::hud::HudPlugin)
+        .add_plugins(ui::nameplate::NameplatePlugin)
+        .add_plugins(ui::console::ConsolePlugin)
+        .add_plugins(lobby::LobbyPlugin)
         .add_plugins(game::GamePlugin)
-        .add_systems(Startup, setup)
-        //.insert_resource(GizmoConfig {
-        //    aabb: AabbGizmoConfig {
-        //        draw_all: true,
-        //        default_color: Some(Color::DARK_GREEN),
-        //    },
-        //    ..Default::default()
-        //})
-        .run();
+        .add_systems(Startup, setup);
+
+    #[cfg(feature = "dev")]
+    app.add_plugins(dev::DevToolsPlugin);
+
+    app.run();
 }
 
 #[derive(Resource)]
@@ -0,0 +1,168 @@
+//! Colliders for the meshes of a scene, one per mesh instead of one giant trimesh for the whole map.
+//!
+//! The node name decides how the collider is built, a blender suffix like `.001` is ignored:
+//! - `*-convex`: convex decomposition, for concave things which should stay cheap
+//! - `*-hull`: a single convex hull
+//! - `*-trimesh`: the exact triangles
+//! - `*-col` / `*-colonly`: invisible proxy, a simplified mesh which is only used as collider
+//! - `*-nocol`: no collider
+//!
+//! A `collider` extra on the node (see `markers`) wins over the name, nodes without either use the
+//! default of the scene. The hint of a node is used for all meshes below it.
+
+use bevy::{prelude::*, scene::SceneInstanceReady};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use super::{ActiveLevel, LevelMap, MapGenerationColliderStatus};
+
+pub const WORLD: Group = Group::GROUP_1;
+pub const PLAYERS: Group = Group::GROUP_2;
+pub const PROPS: Group = Group::GROUP_3;
+pub const TRIGGERS: Group = Group::GROUP_4;
+/// the third person camera is pushed in front of everything in this group
+pub const CAMERA_BLOCKING: Group = Group::GROUP_5;
+
+pub fn world_groups() -> CollisionGroups {
+    CollisionGroups::new(WORLD | CAMERA_BLOCKING, WORLD | PLAYERS | PROPS)
+}
+
+pub fn prop_groups() -> CollisionGroups {
+    CollisionGroups::new(PROPS | CAMERA_BLOCKING, WORLD | PLAYERS | PROPS)
+}
+
+pub fn player_groups() -> CollisionGroups {
+    CollisionGroups::new(PLAYERS, WORLD | PLAYERS | PROPS | TRIGGERS)
+}
+
+pub fn trigger_groups() -> CollisionGroups {
+    CollisionGroups::new(TRIGGERS, PLAYERS)
+}
+
+/// ray casts of the camera only hit solid things which block the view
+pub fn camera_filter() -> QueryFilter<'static> {
+    QueryFilter::new()
+        .groups(CollisionGroups::new(Group::ALL, CAMERA_BLOCKING))
+        .exclude_sensors()
+}
+
+/// the look ray of players stops at everything solid, but not at other players
+pub fn interact_filter() -> QueryFilter<'static> {
+    QueryFilter::new()
+        .groups(CollisionGroups::new(Group::ALL, WORLD | PROPS))
+        .exclude_sensors()
+}
+
+/// how the collider of a scene node should be built
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColliderHint {
+    /// convex decomposition
+    Convex,
+    Hull,
+    Trimesh,
+    /// the mesh is hidden and only used for the collider
+    Proxy,
+    None,
+}
+
+impl ColliderHint {
+    pub fn from_node_name(name: &str) -> Option<Self> {
+        let name = name.split('.').next().unwrap_or_default().to_lowercase();
+        let suffix = name.rsplit(['-', '_']).next().unwrap_or_default();
+        match suffix {
+            "convex" => Some(Self::Convex),
+            "hull" => Some(Self::Hull),
+            "trimesh" => Some(Self::Trimesh),
+            "col" | "colonly" => Some(Self::Proxy),
+            "nocol" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    fn shape(&self) -> Option<ComputedColliderShape> {
+        match self {
+            Self::Convex => Some(ComputedColliderShape::ConvexDecomposition(VHACDParameters::default())),
+            Self::Hull => Some(ComputedColliderShape::ConvexHull),
+            Self::Trimesh | Self::Proxy => Some(ComputedColliderShape::TriMesh),
+            Self::None => None,
+        }
+    }
+}
+
+/// Builds colliders for all meshes of the scene on this entity as soon as the instance is ready.
+#[derive(Component, Clone)]
+pub struct SceneColliders {
+    pub default: ColliderHint,
+    pub groups: CollisionGroups,
+}
+
+pub fn generate_scene_colliders(
+    mut commands: Commands,
+    mut ready_evr: EventReader<SceneInstanceReady>,
+    mut scene_q: Query<(&SceneColliders, Option<&mut LevelMap>)>,
+    children_q: Query<&Children>,
+    parent_q: Query<&Parent>,
+    node_q: Query<(Option<&Name>, Option<&ColliderHint>)>,
+    mesh_q: Query<&Handle<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for ready in ready_evr.read() {
+        let Ok((settings, level_map)) = scene_q.get_mut(ready.parent) else { continue };
+
+        let mut count = 0;
+        for primitive in children_q.iter_descendants(ready.parent) {
+            let Some(mesh) = mesh_q.get(primitive).ok().and_then(|mesh| meshes.get(mesh)) else { continue };
+
+            let hint = hint_for(primitive, ready.parent, &parent_q, &node_q).unwrap_or(settings.default);
+            let Some(shape) = hint.shape() else { continue };
+
+            let Some(collider) = Collider::from_bevy_mesh(mesh, &shape) else {
+                warn!("could not build a {hint:?} collider for {primitive:?}");
+                continue;
+            };
+            commands.entity(primitive).insert((collider, settings.groups));
+            if hint == ColliderHint::Proxy {
+                commands.entity(primitive).insert(Visibility::Hidden);
+            }
+            count += 1;
+        }
+
+        if let Some(mut level_map) = level_map {
+            info!("generated {count} level colliders");
+            level_map.colliders_generated = true;
+        }
+    }
+}
+
+/// the first hint on the way from the mesh up to the scene root
+fn hint_for(
+    primitive: Entity,
+    root: Entity,
+    parent_q: &Query<&Parent>,
+    node_q: &Query<(Option<&Name>, Option<&ColliderHint>)>,
+) -> Option<ColliderHint> {
+    let mut node = primitive;
+    while node != root {
+        if let Ok((name, hint)) = node_q.get(node) {
+            if let Some(hint) = hint {
+                return Some(*hint);
+            }
+            if let Some(hint) = name.and_then(|name| ColliderHint::from_node_name(name.as_str())) {
+                return Some(hint);
+            }
+        }
+        node = parent_q.get(node).ok()?.get();
+    }
+    None
+}
+
+/// true as soon as the floor and the walls of the level exist, so nobody falls through the map
+pub fn colliders_ready(
+    active_level: Option<Res<ActiveLevel>>,
+    level_q: Query<&LevelMap>,
+    mut status: ResMut<MapGenerationColliderStatus>,
+) -> iyes_progress::Progress {
+    status.already_generated = active_level.is_some() && level_q.iter().all(|level_map| level_map.colliders_generated);
+    status.already_generated.into()
+}
@@ -0,0 +1,73 @@
+//! Doors and gates of the kit. They swing around a hinge as kinematic bodies, so they push players
+//! out of the way but are never pushed themselves.
+//!
+//! The state of a door is the frame counter of its swing, it is rolled back like the rest of the
+//! simulation and the rotation is derived from it in the `GgrsSchedule`.
+
+use bevy::prelude::*;
+
+/// how far a door opens
+const OPEN_ANGLE: f32 = 100.0_f32 * std::f32::consts::PI / 180.0;
+
+/// frames a door needs to open or close
+pub const SWING_FRAMES: u32 = 30;
+
+/// A door of the level which can be opened by triggers and players, part of the rollback state.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Door {
+    pub id: String,
+    pub open: bool,
+    /// 0 is closed, `SWING_FRAMES` is fully open
+    pub swing: u32,
+}
+
+impl Door {
+    pub fn new(id: String) -> Self {
+        Self {
+            id,
+            open: false,
+            swing: 0,
+        }
+    }
+
+    pub fn angle(&self) -> f32 {
+        // ease in and out, so the door doesn't stop abruptly
+        let t = self.swing as f32 / SWING_FRAMES as f32;
+        OPEN_ANGLE * t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// the hinge of a door, the door model is a child of it
+#[derive(Component, Clone, Copy, Debug)]
+pub struct DoorHinge {
+    pub closed: Quat,
+}
+
+/// The hinge of the door models in model space, the whole model swings around it.
+pub fn door_hinge(model: &str) -> Option<Vec3> {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    let name = name.strip_suffix(".glb").unwrap_or(name);
+
+    DOOR_KIT.iter().find(|(kit_name, _)| *kit_name == name).map(|(_, hinge)| *hinge)
+}
+
+const DOOR_KIT: &[(&str, Vec3)] = &[
+    ("crypt-door", Vec3::new(0.0, 0.0, 0.0)),
+    ("fence-gate", Vec3::new(-0.5, 0.0, -0.5)),
+    ("iron-fence-border-gate", Vec3::new(-0.5, 0.0, -0.45)),
+];
+
+pub fn swing_doors(mut door_q: Query<(&mut Door, &DoorHinge, &mut Transform)>) {
+    for (mut door, hinge, mut transform) in door_q.iter_mut() {
+        let swing = if door.open {
+            (door.swing + 1).min(SWING_FRAMES)
+        } else {
+            door.swing.saturating_sub(1)
+        };
+        // rapier only updates kinematic bodies whose transform changed
+        if swing != door.swing {
+            door.swing = swing;
+            transform.rotation = hinge.closed * Quat::from_rotation_y(door.angle());
+        }
+    }
+}
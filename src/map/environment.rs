@@ -0,0 +1,125 @@
+//! The sky of the level and the light it casts on the scene.
+//!
+//! The skybox images are stacked PNGs which are turned into cube textures when they are loaded. The
+//! active 3d camera gets the `Skybox`, and an `EnvironmentMapLight` if the level has prefiltered
+//! maps for the diffuse and specular light. A level without a usable sky gets its plain `sky_color`.
+
+use bevy::{
+    asset::LoadState,
+    core_pipeline::Skybox,
+    pbr::EnvironmentMapLight,
+    prelude::*,
+    render::render_resource::{TextureViewDescriptor, TextureViewDimension},
+};
+
+use crate::Cubemap;
+
+/// Turns the images of the cubemap into cube textures as soon as they are loaded.
+/// Images which are missing or have the wrong size are dropped, without a day sky there is no skybox.
+pub fn prepare_cubemap(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut cubemap: ResMut<Cubemap>,
+) {
+    if cubemap.is_loaded {
+        return;
+    }
+
+    let mut handles: Vec<&Handle<Image>> = cubemap.images.iter().collect();
+    if let Some((diffuse, specular)) = &cubemap.environment_map {
+        handles.extend([diffuse, specular]);
+    }
+    let states: Vec<LoadState> = handles.iter().map(|handle| asset_server.load_state(*handle)).collect();
+    if states.iter().any(|state| *state != LoadState::Loaded && *state != LoadState::Failed) {
+        return;
+    }
+
+    let usable = |handle: &Handle<Image>, images: &mut Assets<Image>| -> bool {
+        let Some(image) = images.get_mut(handle) else {
+            warn!("sky image {:?} could not be loaded", handle.path());
+            return false;
+        };
+        if !make_cube(image) {
+            warn!("sky image {:?} is not six square faces stacked on top of each other", handle.path());
+            return false;
+        }
+        true
+    };
+
+    if let Some((diffuse, specular)) = cubemap.environment_map.clone() {
+        if !(usable(&diffuse, &mut images) & usable(&specular, &mut images)) {
+            warn!("the level has no usable environment map, the sky is used for the lighting");
+            cubemap.environment_map = None;
+        }
+    }
+
+    let day = cubemap.images[0].clone();
+    if !usable(&day, &mut images) {
+        commands.remove_resource::<Cubemap>();
+        return;
+    }
+    // a broken night sky only means that the day sky stays
+    let night_usable = cubemap.images.get(1).cloned().map(|night| usable(&night, &mut images));
+    if night_usable == Some(false) {
+        cubemap.images.truncate(1);
+        cubemap.index = 0;
+    }
+
+    cubemap.is_loaded = true;
+}
+
+fn make_cube(image: &mut Image) -> bool {
+    if image.texture_descriptor.array_layer_count() == 6 {
+        return true;
+    }
+    if image.texture_descriptor.array_layer_count() != 1 || image.height() != image.width() * 6 {
+        return false;
+    }
+
+    // NOTE: PNGs do not have any metadata that could indicate they contain a cubemap texture,
+    // so they appear as one texture. The following code reconfigures the texture as necessary.
+    image.reinterpret_stacked_2d_as_array(image.height() / image.width());
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    true
+}
+
+/// keeps the sky of the active camera in line with the cubemap, also after the sky changed
+pub fn sync_camera_environment(
+    mut commands: Commands,
+    cubemap: Option<Res<Cubemap>>,
+    camera_q: Query<(Entity, &Camera, Option<&Skybox>), With<Camera3d>>,
+) {
+    let sky = cubemap.as_ref().filter(|cubemap| cubemap.is_loaded).map(|cubemap| cubemap.image());
+
+    for (e, camera, skybox) in camera_q.iter() {
+        match (sky.as_ref().filter(|_| camera.is_active), skybox) {
+            (Some(sky), Some(skybox)) if skybox.0 == *sky => {}
+            (Some(sky), _) => {
+                let cubemap = cubemap.as_ref().unwrap();
+                commands.entity(e).insert(Skybox(sky.clone()));
+                // the environment map belongs to the day sky, the sky image itself has no mips and no
+                // convolution, without prefiltered maps the ambient light does the job
+                match cubemap.environment_map.clone().filter(|_| cubemap.index == 0) {
+                    Some((diffuse_map, specular_map)) => {
+                        commands.entity(e).insert(EnvironmentMapLight {
+                            diffuse_map,
+                            specular_map,
+                        });
+                    }
+                    None => {
+                        commands.entity(e).remove::<EnvironmentMapLight>();
+                    }
+                }
+            }
+            (None, Some(_)) => {
+                commands.entity(e).remove::<(Skybox, EnvironmentMapLight)>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
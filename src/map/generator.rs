@@ -0,0 +1,384 @@
+//! Procedural graveyard, built from the same prop kit as `graveyard.level.ron`.
+//!
+//! The layout only depends on the seed, so the peers just have to agree on the seed in the lobby.
+//! Everything is decided with integer math, in whole tiles and centimeters, and the props only
+//! turn in quarter turns, which `LevelTransform` takes from a table instead of sin / cos. The only
+//! float math is the conversion to meters when the level is built, a division which is rounded the
+//! same on every platform.
+
+use bevy::prelude::*;
+
+use super::level::{DayCycle, Ground, Level, LevelTransform, PropPlacement};
+
+/// size of one kit tile in centimeters, the models are 1 unit and scaled by `PROP_SCALE`
+const TILE: i32 = 300;
+const PROP_SCALE: f32 = 3.0;
+/// plots are squares of 3x3 tiles
+const PLOT: i32 = 3;
+/// length of one road piece in centimeters
+const ROAD_STEP: i32 = 225;
+
+const GRAVESTONES: &[&str] = &[
+    "gravestone-bevel.glb",
+    "gravestone-cross.glb",
+    "gravestone-decorative.glb",
+    "gravestone-roof.glb",
+    "gravestone-round.glb",
+    "gravestone-wide.glb",
+    "gravestone-broken.glb",
+];
+const TREES: &[&str] = &["pine.glb", "pine-crooked.glb"];
+const ROCKS: &[&str] = &["rocks.glb", "rocks-tall.glb"];
+
+/// splitmix64, small and portable. The output for a seed must never change, else peers with
+/// different versions would build different maps.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// uniform in `0..n`
+    pub fn below(&mut self, n: u32) -> i32 {
+        (self.next_u64() % n.max(1) as u64) as i32
+    }
+
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.below(100) < percent as i32
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u32) as usize]
+    }
+}
+
+/// which tiles are already used by paths, the border or plots
+struct Grid {
+    half: i32,
+    used: Vec<bool>,
+}
+
+impl Grid {
+    fn new(half: i32) -> Self {
+        let size = (half * 2) as usize;
+        Self { half, used: vec![false; size * size] }
+    }
+
+    fn index(&self, i: i32, j: i32) -> Option<usize> {
+        let size = self.half * 2;
+        let (x, z) = (i + self.half, j + self.half);
+        (x >= 0 && z >= 0 && x < size && z < size).then(|| (z * size + x) as usize)
+    }
+
+    fn is_free(&self, i: i32, j: i32) -> bool {
+        self.index(i, j).map(|index| !self.used[index]).unwrap_or(false)
+    }
+
+    fn mark(&mut self, i: i32, j: i32) {
+        if let Some(index) = self.index(i, j) {
+            self.used[index] = true;
+        }
+    }
+
+    fn area_is_free(&self, i: i32, j: i32, size: i32) -> bool {
+        (0..size).all(|dz| (0..size).all(|dx| self.is_free(i + dx, j + dz)))
+    }
+}
+
+/// center of the tile with index `i` in centimeters, tile 0 starts at the origin
+fn center(i: i32) -> i32 {
+    i * TILE + TILE / 2
+}
+
+fn meters(centimeters: i32) -> f32 {
+    centimeters as f32 / 100.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlotKind {
+    Graves,
+    Crypt,
+    Trees,
+}
+
+/// The tiles the seed decides, the props are placed on them afterwards.
+#[derive(Debug, PartialEq)]
+struct Layout {
+    /// the graveyard goes from tile `-half` to `half - 1` in both directions
+    half: i32,
+    /// column of the main path from the gate to the north
+    path_column: i32,
+    cross_row: i32,
+    /// tile of the corner with the lowest indices
+    plots: Vec<(i32, i32, PlotKind)>,
+}
+
+impl Layout {
+    fn generate(rng: &mut SplitMix64) -> Self {
+        let half = 7 + rng.below(4);
+        let mut grid = Grid::new(half);
+
+        // the gate is in the south fence, the main path leads from there to the north fence
+        let path_column = rng.below((half as u32 - 3) * 2) - half + 3;
+        let cross_row = rng.below((half as u32 - 4) * 2) - half + 4;
+
+        for i in -half..half {
+            for j in [-half, half - 1] {
+                grid.mark(i, j);
+                grid.mark(j, i);
+            }
+            grid.mark(path_column, i);
+            grid.mark(i, cross_row);
+        }
+
+        let mut plots = Vec::new();
+        for j in (-half + 1)..(half - PLOT) {
+            for i in (-half + 1)..(half - PLOT) {
+                if !grid.area_is_free(i, j, PLOT) {
+                    continue;
+                }
+                for dz in 0..PLOT {
+                    for dx in 0..PLOT {
+                        grid.mark(i + dx, j + dz);
+                    }
+                }
+
+                let kind = match rng.below(100) {
+                    0..=54 => PlotKind::Graves,
+                    55..=74 => PlotKind::Crypt,
+                    _ => PlotKind::Trees,
+                };
+                plots.push((i, j, kind));
+            }
+        }
+
+        Self { half, path_column, cross_row, plots }
+    }
+}
+
+/// `x`, `y` and `z` in centimeters, the rotation in counterclockwise quarter turns around y
+#[derive(Default)]
+struct Props(Vec<PropPlacement>);
+
+impl Props {
+    fn place(&mut self, model: &str, x: i32, z: i32, turns: i32) {
+        self.place_at(model, x, 0, z, turns);
+    }
+
+    fn place_at(&mut self, model: &str, x: i32, y: i32, z: i32, turns: i32) {
+        self.0.push(PropPlacement {
+            model: model.to_string(),
+            transform: LevelTransform {
+                translation: Vec3::new(meters(x), meters(y), meters(z)),
+                rotation: (turns.rem_euclid(4) * 90) as f32,
+                ..Default::default()
+            },
+            id: None,
+        });
+    }
+}
+
+/// Builds a fenced graveyard with a main path from the gate to the north, one cross path and
+/// plots with graves, crypts or trees in between.
+pub fn generate_graveyard(seed: u64) -> Level {
+    let mut rng = SplitMix64::new(seed);
+    let layout = Layout::generate(&mut rng);
+    let mut props = Props::default();
+
+    let Layout { half, path_column, cross_row, .. } = layout;
+    let extent = half * TILE;
+    let path_x = center(path_column);
+    let cross_z = center(cross_row);
+
+    for i in -half..half {
+        props.place("iron-fence-border.glb", center(i), center(-half), 0);
+        props.place("iron-fence-border.glb", center(half - 1), center(i), -1);
+        props.place("iron-fence-border.glb", center(-half), center(i), 1);
+
+        let south = if i == path_column {
+            "iron-fence-border-gate.glb"
+        } else if i == path_column + 1 {
+            "iron-fence-border-column.glb"
+        } else {
+            "iron-fence-border.glb"
+        };
+        props.place(south, center(i), center(half - 1), 2);
+    }
+
+    let road_length = extent * 2 - TILE * 2;
+    for n in 0..=road_length / ROAD_STEP {
+        let offset = n * ROAD_STEP;
+        props.place("road.glb", path_x, extent - TILE - offset, 0);
+
+        let x = -extent + TILE + offset;
+        // the main path already covers the crossing
+        if (x - path_x).abs() > 120 {
+            props.place("road.glb", x, cross_z, 1);
+        }
+    }
+
+    // lightposts on alternating sides of the main path, the arm points over the road
+    for (n, j) in ((-half + 2)..(half - 1)).step_by(3).enumerate() {
+        if j == cross_row {
+            continue;
+        }
+        let (side, turns) = if n % 2 == 0 { (-140, 1) } else { (140, -1) };
+        props.place("lightpost-single.glb", path_x + side, center(j), turns);
+    }
+
+    for &(i, j, kind) in layout.plots.iter() {
+        match kind {
+            PlotKind::Graves => grave_plot(&mut rng, &mut props, i, j, path_x),
+            PlotKind::Crypt => crypt_plot(&mut rng, &mut props, i, j, path_x),
+            PlotKind::Trees => tree_plot(&mut rng, &mut props, i, j),
+        }
+    }
+
+    Level {
+        name: format!("graveyard #{seed}"),
+        scene: None,
+        ground: Some(Ground {
+            size: Vec2::splat(meters(extent * 2 + TILE * 2)),
+            height: 0.0,
+            color: [0.22, 0.26, 0.18],
+        }),
+        props: props.0,
+        prop_scale: PROP_SCALE,
+        lights: Vec::new(),
+        colliders: Vec::new(),
+        spawn_points: vec![
+            Vec3::new(meters(path_x), 1.5, meters(extent - TILE * 3 / 2)),
+            Vec3::new(meters(path_x), 1.5, meters(cross_z)),
+            Vec3::new(meters(-extent + TILE * 3 / 2), 1.5, meters(cross_z)),
+            Vec3::new(meters(extent - TILE * 3 / 2), 1.5, meters(cross_z)),
+        ],
+        triggers: Vec::new(),
+        skybox: Some("Textures/Ryfjallet_cubemap.png".to_string()),
+        night_skybox: None,
+        environment_map: None,
+        sky_color: [0.2, 0.22, 0.3],
+        // starts in the evening, so the lanterns come on during the match
+        day_cycle: DayCycle {
+            start_hour: 18.5,
+            day_length: 1200.0,
+        },
+        origin: Vec3::ZERO,
+    }
+}
+
+/// wooden fence around two rows of graves, the gate faces the main path
+fn grave_plot(rng: &mut SplitMix64, props: &mut Props, i: i32, j: i32, path_x: i32) {
+    let gate_east = center(i + 1) < path_x;
+
+    for k in 0..PLOT {
+        let fence = |rng: &mut SplitMix64| if rng.chance(10) { "fence-damaged.glb" } else { "fence.glb" };
+        props.place(fence(rng), center(i + k), center(j), 0);
+        props.place(fence(rng), center(i + k), center(j + PLOT - 1), 2);
+
+        let west = if k == 1 && !gate_east { "fence-gate.glb" } else { fence(rng) };
+        props.place(west, center(i), center(j + k), 1);
+        let east = if k == 1 && gate_east { "fence-gate.glb" } else { fence(rng) };
+        props.place(east, center(i + PLOT - 1), center(j + k), -1);
+    }
+
+    let z0 = j * TILE;
+    for row in [320, 700] {
+        for k in 0..PLOT {
+            if !rng.chance(80) {
+                continue;
+            }
+            let x = center(i + k);
+            props.place("grave.glb", x, z0 + row, 0);
+            props.place(rng.pick(GRAVESTONES), x, z0 + row - 180, 0);
+        }
+    }
+}
+
+/// a small crypt with its entrance to the main path
+fn crypt_plot(rng: &mut SplitMix64, props: &mut Props, i: i32, j: i32, path_x: i32) {
+    let (x, z) = (center(i + 1), center(j + 1));
+    let (side, turns) = if x < path_x { (1, 1) } else { (-1, -1) };
+
+    props.place("crypt-small.glb", x, z, turns);
+    props.place_at("crypt-small-roof.glb", x, 300, z, turns);
+    props.place("lantern-candle.glb", x + side * 250, z + 300, 0);
+    if rng.chance(50) {
+        props.place("candle-multiple.glb", x + side * 250, z - 300, 0);
+    }
+}
+
+fn tree_plot(rng: &mut SplitMix64, props: &mut Props, i: i32, j: i32) {
+    let (x, z) = (center(i + 1), center(j + 1));
+
+    for _ in 0..2 + rng.below(3) {
+        let dx = (rng.below(13) - 6) * 50;
+        let dz = (rng.below(13) - 6) * 50;
+        let turns = rng.below(4);
+        props.place(rng.pick(TREES), x + dx, z + dz, turns);
+    }
+    if rng.chance(50) {
+        let dx = (rng.below(9) - 4) * 50;
+        let dz = (rng.below(9) - 4) * 50;
+        let turns = rng.below(4);
+        props.place(rng.pick(ROCKS), x + dx, z + dz, turns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pinned output for a seed. If this breaks, peers with the old and the new version build
+    /// different maps for the same seed.
+    #[test]
+    fn seed_has_a_fixed_layout() {
+        use PlotKind::*;
+
+        let layout = Layout::generate(&mut SplitMix64::new(42));
+        assert_eq!((layout.half, layout.path_column, layout.cross_row), (8, -4, -2));
+        assert_eq!(
+            layout.plots,
+            vec![
+                (-7, -7, Crypt),
+                (-3, -7, Graves),
+                (0, -7, Crypt),
+                (3, -7, Graves),
+                (-7, -1, Graves),
+                (-3, -1, Graves),
+                (0, -1, Crypt),
+                (3, -1, Graves),
+                (-7, 2, Graves),
+                (-3, 2, Trees),
+                (0, 2, Trees),
+                (3, 2, Crypt),
+            ]
+        );
+        assert_eq!(generate_graveyard(42).props.len(), 249);
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        assert_ne!(generate_graveyard(1).props, generate_graveyard(2).props);
+    }
+
+    #[test]
+    fn spawn_points_are_on_the_ground() {
+        for seed in 0..32 {
+            let level = generate_graveyard(seed);
+            let ground = level.ground.as_ref().unwrap();
+            assert!(level.spawn_points.len() >= 2);
+            for point in level.spawn_points.iter() {
+                assert!(point.x.abs() < ground.size.x / 2.0 && point.z.abs() < ground.size.y / 2.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// A level file (`assets/levels/*.level.ron`) which describes everything that gets spawned for a map.
+#[derive(Asset, TypePath, Debug, PartialEq, Deserialize)]
+pub struct Level {
+    pub name: String,
+    #[serde(default)]
+    pub scene: Option<LevelScene>,
+    #[serde(default)]
+    pub ground: Option<Ground>,
+    #[serde(default)]
+    pub props: Vec<PropPlacement>,
+    /// uniform scale for all props, the kit models are built around 1 unit tiles
+    #[serde(default = "default_prop_scale")]
+    pub prop_scale: f32,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    #[serde(default)]
+    pub colliders: Vec<ColliderDescription>,
+    #[serde(default)]
+    pub spawn_points: Vec<Vec3>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerDescription>,
+    #[serde(default)]
+    pub skybox: Option<String>,
+    /// shown instead of `skybox` between dusk and dawn
+    #[serde(default)]
+    pub night_skybox: Option<String>,
+    /// diffuse and specular light of the sky, stacked PNGs like the skybox
+    #[serde(default)]
+    pub environment_map: Option<EnvironmentMapDescription>,
+    /// shown behind everything if the level has no skybox or it can't be loaded
+    #[serde(default = "default_sky_color")]
+    pub sky_color: [f32; 3],
+    #[serde(default)]
+    pub day_cycle: DayCycle,
+    /// Point of the file coordinates which becomes the world origin. Maps which are modeled far away
+    /// from zero get jittery in f32, so everything is moved by `-origin` when the level is loaded.
+    /// Meshes of the scene which were modeled far away get their vertices moved when it is spawned.
+    #[serde(default)]
+    pub origin: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EnvironmentMapDescription {
+    pub diffuse: String,
+    pub specular: String,
+}
+
+fn default_sky_color() -> [f32; 3] {
+    [0.45, 0.55, 0.7]
+}
+
+/// The time of day during a match, it is derived from the match clock.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DayCycle {
+    /// hour when the match starts, 0 to 24
+    pub start_hour: f32,
+    /// seconds for a whole day, 0 lets the time stand still
+    pub day_length: f32,
+}
+
+impl Default for DayCycle {
+    fn default() -> Self {
+        Self {
+            start_hour: 14.0,
+            day_length: 0.0,
+        }
+    }
+}
+
+fn default_prop_scale() -> f32 {
+    1.0
+}
+
+impl Level {
+    /// moves the level so `origin` ends up at zero, only the level file knows the big coordinates
+    pub fn normalize(&mut self) {
+        let offset = -self.origin;
+        if offset == Vec3::ZERO {
+            return;
+        }
+
+        if let Some(scene) = &mut self.scene {
+            scene.transform.translation += offset;
+        }
+        if let Some(ground) = &mut self.ground {
+            ground.height += offset.y;
+        }
+        for prop in self.props.iter_mut() {
+            prop.transform.translation += offset;
+        }
+        for light in self.lights.iter_mut() {
+            light.position += offset;
+        }
+        for collider in self.colliders.iter_mut() {
+            collider.transform.translation += offset;
+        }
+        for spawn_point in self.spawn_points.iter_mut() {
+            *spawn_point += offset;
+        }
+        for trigger in self.triggers.iter_mut() {
+            if let Some(volume) = &mut trigger.volume {
+                volume.transform.translation += offset;
+            }
+            for action in trigger.actions_mut() {
+                if let TriggerAction::Teleport { to } = action {
+                    *to += offset;
+                }
+            }
+        }
+        self.origin = Vec3::ZERO;
+    }
+
+    /// spawn point for a player handle, wraps around if there are more players than spawn points
+    pub fn spawn_point(&self, handle: usize) -> Vec3 {
+        if self.spawn_points.is_empty() {
+            return Vec3::Y;
+        }
+        self.spawn_points[handle % self.spawn_points.len()]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LevelTransform {
+    pub translation: Vec3,
+    /// rotation around the y axis in degrees
+    pub rotation: f32,
+    pub scale: Vec3,
+}
+
+impl Default for LevelTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: 0.0,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl From<&LevelTransform> for Transform {
+    fn from(t: &LevelTransform) -> Self {
+        Transform::from_translation(t.translation)
+            .with_rotation(rotation_y(t.rotation))
+            .with_scale(t.scale)
+    }
+}
+
+/// Quarter turns come from a table, so a generated level doesn't depend on the sin / cos of the
+/// platform, see `generator`.
+fn rotation_y(degrees: f32) -> Quat {
+    const HALF_SQRT_2: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const QUARTER_TURNS: [Quat; 4] = [
+        Quat::from_xyzw(0.0, 0.0, 0.0, 1.0),
+        Quat::from_xyzw(0.0, HALF_SQRT_2, 0.0, HALF_SQRT_2),
+        Quat::from_xyzw(0.0, 1.0, 0.0, 0.0),
+        Quat::from_xyzw(0.0, HALF_SQRT_2, 0.0, -HALF_SQRT_2),
+    ];
+    if degrees % 90.0 == 0.0 {
+        return QUARTER_TURNS[(degrees / 90.0).rem_euclid(4.0) as usize];
+    }
+    Quat::from_rotation_y(degrees.to_radians())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub enum SceneCollider {
+    #[default]
+    TriMesh,
+    ConvexHull,
+    None,
+}
+
+/// the base scene of the level, e.g. a whole map exported from blender
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LevelScene {
+    pub path: String,
+    #[serde(default)]
+    pub transform: LevelTransform,
+    #[serde(default)]
+    pub collider: SceneCollider,
+}
+
+/// a flat floor with a collider, for levels which are built only from props
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Ground {
+    pub size: Vec2,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default = "default_ground_color")]
+    pub color: [f32; 3],
+}
+
+fn default_ground_color() -> [f32; 3] {
+    [0.3, 0.3, 0.3]
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PropPlacement {
+    /// the gltf file of the prop, Scene0 is spawned
+    pub model: String,
+    #[serde(default)]
+    pub transform: LevelTransform,
+    /// name for triggers, e.g. of a door
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LightDescription {
+    /// lights with a name can be switched by triggers
+    #[serde(default)]
+    pub name: Option<String>,
+    pub position: Vec3,
+    #[serde(default = "default_light_color")]
+    pub color: [f32; 3],
+    #[serde(default = "default_light_intensity")]
+    pub intensity: f32,
+    #[serde(default = "default_light_range")]
+    pub range: f32,
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+/// the defaults of lights in the level file, also used for the lights in the extras of the scene
+pub fn default_light_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+pub fn default_light_intensity() -> f32 {
+    800.0
+}
+
+pub fn default_light_range() -> f32 {
+    20.0
+}
+
+impl LightDescription {
+    pub fn point_light(&self) -> PointLight {
+        PointLight {
+            intensity: self.intensity,
+            range: self.range,
+            color: Color::rgb(self.color[0], self.color[1], self.color[2]),
+            shadows_enabled: self.shadows,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum ColliderShape {
+    Cuboid { half_extents: Vec3 },
+    Ball { radius: f32 },
+    Cylinder { half_height: f32, radius: f32 },
+    Capsule { half_height: f32, radius: f32 },
+}
+
+impl ColliderShape {
+    pub fn collider(&self) -> Collider {
+        match self {
+            ColliderShape::Cuboid { half_extents } => Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            ColliderShape::Ball { radius } => Collider::ball(*radius),
+            ColliderShape::Cylinder { half_height, radius } => Collider::cylinder(*half_height, *radius),
+            ColliderShape::Capsule { half_height, radius } => Collider::capsule_y(*half_height, *radius),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ColliderDescription {
+    pub shape: ColliderShape,
+    #[serde(default)]
+    pub transform: LevelTransform,
+}
+
+/// Actions of a trigger volume, either placed here or in the level scene (then only the `id` is given).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TriggerDescription {
+    pub id: String,
+    #[serde(default)]
+    pub volume: Option<TriggerVolume>,
+    #[serde(default)]
+    pub on_enter: Vec<TriggerAction>,
+    #[serde(default)]
+    pub on_exit: Vec<TriggerAction>,
+    /// runs every frame while somebody is inside
+    #[serde(default)]
+    pub on_stay: Vec<TriggerAction>,
+}
+
+impl TriggerDescription {
+    fn actions_mut(&mut self) -> impl Iterator<Item = &mut TriggerAction> {
+        self.on_enter.iter_mut().chain(self.on_exit.iter_mut()).chain(self.on_stay.iter_mut())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TriggerVolume {
+    #[serde(default)]
+    pub transform: LevelTransform,
+    pub half_extents: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum TriggerAction {
+    /// only local, played at the trigger
+    PlaySound { path: String },
+    /// only local, switches the light with this name on or off
+    ToggleLight { target: String },
+    OpenDoor { door: String },
+    StartRound,
+    Teleport { to: Vec3 },
+    /// health which is lost once, or per second in `on_stay`
+    Damage { amount: f32 },
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+#[derive(Debug, Error)]
+pub enum LevelLoaderError {
+    #[error("could not read level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Level, LevelLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut level = ron::de::from_bytes::<Level>(&bytes)?;
+            level.normalize();
+            Ok(level)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
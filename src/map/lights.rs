@@ -0,0 +1,172 @@
+//! Point lights of the level go through a `LightEmitter`, which lets flames flicker and keeps the
+//! number of lights and shadow maps in budget. Only the lights closest to the camera are shown and
+//! only the closest of those which want shadows get them.
+//!
+//! The flicker is a local effect, it doesn't need to be the same on every peer.
+
+use bevy::prelude::*;
+
+use super::time_of_day::TimeOfDay;
+
+/// How a flame changes over time, driven by noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flicker {
+    /// relative change of the intensity, 0.2 is +-20%
+    pub amount: f32,
+    /// how fast the noise changes, in changes per second
+    pub speed: f32,
+    /// how much the flame gets redder when it is dim
+    pub color_shift: f32,
+}
+
+impl Flicker {
+    pub const CANDLE: Flicker = Flicker {
+        amount: 0.25,
+        speed: 9.0,
+        color_shift: 0.15,
+    };
+    /// the glass keeps the wind away
+    pub const LANTERN: Flicker = Flicker {
+        amount: 0.1,
+        speed: 6.0,
+        color_shift: 0.08,
+    };
+    pub const FIRE: Flicker = Flicker {
+        amount: 0.35,
+        speed: 5.0,
+        color_shift: 0.3,
+    };
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct LightEmitter {
+    pub intensity: f32,
+    pub color: Color,
+    pub flicker: Option<Flicker>,
+    /// the light gets shadows if it is close enough, see `LightBudget`
+    pub shadows: bool,
+    /// switched by triggers, the time of day switches all lights on top of it
+    pub on: bool,
+}
+
+impl LightEmitter {
+    pub fn new(light: &PointLight, flicker: Option<Flicker>) -> Self {
+        Self {
+            intensity: light.intensity,
+            color: light.color,
+            flicker,
+            shadows: light.shadows_enabled,
+            on: true,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct LightBudget {
+    /// lights further away from the camera are hidden
+    pub max_distance: f32,
+    /// only the closest lights are shown, the rest are hidden
+    pub max_lights: usize,
+    /// every point light shadow is a cube map, they are expensive
+    pub max_shadows: usize,
+}
+
+impl Default for LightBudget {
+    fn default() -> Self {
+        Self {
+            max_distance: 45.0,
+            max_lights: 48,
+            max_shadows: 4,
+        }
+    }
+}
+
+/// a random value between -1 and 1 for every integer
+fn hash(i: i32) -> f32 {
+    let mut x = i as u32;
+    x = (x ^ 61) ^ (x >> 16);
+    x = x.wrapping_mul(9);
+    x ^= x >> 4;
+    x = x.wrapping_mul(0x27d4_eb2d);
+    x ^= x >> 15;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+fn value_noise(t: f32) -> f32 {
+    let i = t.floor();
+    let f = t - i;
+    let f = f * f * (3.0 - 2.0 * f);
+    let i = i as i32;
+    hash(i) + (hash(i.wrapping_add(1)) - hash(i)) * f
+}
+
+/// a fixed value between 0 and 1 for every position, so not all lights do the same
+fn position_seed(p: Vec3) -> f32 {
+    ((p.x * 12.9898 + p.y * 4.1414 + p.z * 78.233).sin() * 437.585).fract().abs()
+}
+
+/// two octaves, a slow wavering and a fast crackle
+fn flicker_noise(t: f32) -> f32 {
+    0.7 * value_noise(t) + 0.3 * value_noise(t * 2.9 + 17.0)
+}
+
+pub fn update_light_emitters(
+    time: Res<Time>,
+    budget: Res<LightBudget>,
+    time_of_day: Res<TimeOfDay>,
+    camera_q: Query<&GlobalTransform, With<Camera3d>>,
+    mut light_q: Query<(Entity, &LightEmitter, &GlobalTransform, &mut PointLight, &mut Visibility)>,
+    mut visible: Local<Vec<(f32, Entity, bool)>>,
+) {
+    let Some(camera) = camera_q.iter().next() else { return };
+    let camera = camera.translation();
+
+    // the closest lights are shown, the closest shadow casters of them get their shadows
+    visible.clear();
+    for (e, emitter, transform, _, _) in light_q.iter() {
+        let distance = transform.translation().distance(camera);
+        let lit = emitter.on && time_of_day.lights_on(position_seed(transform.translation()));
+        if lit && distance < budget.max_distance {
+            visible.push((distance, e, emitter.shadows));
+        }
+    }
+    visible.sort_by(|a, b| a.0.total_cmp(&b.0));
+    visible.truncate(budget.max_lights);
+    let mut shadows = 0;
+    for (_, _, wants_shadows) in visible.iter_mut() {
+        *wants_shadows = *wants_shadows && shadows < budget.max_shadows;
+        if *wants_shadows {
+            shadows += 1;
+        }
+    }
+
+    let elapsed = time.elapsed_seconds();
+    for (e, emitter, transform, mut light, mut visibility) in light_q.iter_mut() {
+        let shown = visible.iter().find(|(_, v, _)| *v == e);
+        let wanted = if shown.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+        let Some((_, _, shadows_enabled)) = shown else { continue };
+        if light.shadows_enabled != *shadows_enabled {
+            light.shadows_enabled = *shadows_enabled;
+        }
+
+        if let Some(flicker) = emitter.flicker {
+            // every flame gets its own part of the noise
+            let offset = position_seed(transform.translation()) * 1000.0;
+            let n = flicker_noise(elapsed * flicker.speed + offset);
+
+            light.intensity = emitter.intensity * (1.0 + flicker.amount * n);
+            let dim = flicker.color_shift * (0.5 - 0.5 * n);
+            light.color = Color::rgb(
+                emitter.color.r(),
+                emitter.color.g() * (1.0 - dim),
+                emitter.color.b() * (1.0 - 2.0 * dim).max(0.0),
+            );
+        } else if light.intensity != emitter.intensity {
+            light.intensity = emitter.intensity;
+            light.color = emitter.color;
+        }
+    }
+}
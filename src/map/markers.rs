@@ -0,0 +1,162 @@
+//! Level authors mark nodes in blender, either by name (`spawn_point.001`) or with custom
+//! properties which end up in the gltf `extras`:
+//!
+//! - `spawn_point: true` or `spawn_point: <index>`
+//! - `light: { color: [r, g, b], intensity, range, shadows }`
+//! - `trigger: { id: "crypt_entrance", half_extents: [x, y, z] }`, a blender cube has half extents of 1
+//! - `collider: "convex"` / `"hull"` / `"trimesh"` / `"proxy"` / `"none"`, see `colliders`
+//!
+//! The markers are turned into components as soon as the scene instance of the level is ready.
+
+use bevy::{gltf::GltfExtras, prelude::*, scene::SceneInstanceReady};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    colliders::{self, ColliderHint},
+    level::{default_light_color, default_light_intensity, default_light_range},
+    lights::LightEmitter,
+    LevelMap,
+};
+
+/// a player spawn placed in the level scene, spawns without index come after the numbered ones
+#[derive(Component, Debug, Clone)]
+pub struct SpawnPoint {
+    pub index: Option<usize>,
+}
+
+/// A sensor volume from the level scene, the `id` is used to find its actions.
+#[derive(Component, Debug, Clone)]
+pub struct LevelTrigger {
+    pub id: String,
+    pub half_extents: Vec3,
+}
+
+/// Spawn points of the scene in the order of their index, they win over the ones in the level file.
+#[derive(Resource, Debug, Clone)]
+pub struct SceneSpawnPoints(pub Vec<Vec3>);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NodeExtras {
+    /// `true` or the index of the spawn point
+    spawn_point: Option<serde_json::Value>,
+    light: Option<NodeLight>,
+    trigger: Option<NodeTrigger>,
+    collider: Option<ColliderHint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeLight {
+    #[serde(default = "default_light_color")]
+    color: [f32; 3],
+    #[serde(default = "default_light_intensity")]
+    intensity: f32,
+    #[serde(default = "default_light_range")]
+    range: f32,
+    #[serde(default)]
+    shadows: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeTrigger {
+    id: String,
+    #[serde(default = "default_half_extents")]
+    half_extents: [f32; 3],
+}
+
+fn default_half_extents() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+pub fn parse_level_markers(
+    mut commands: Commands,
+    mut ready_evr: EventReader<SceneInstanceReady>,
+    mut level_q: Query<&mut LevelMap>,
+    children_q: Query<&Children>,
+    node_q: Query<(Option<&Name>, Option<&GltfExtras>, &GlobalTransform)>,
+) {
+    for ready in ready_evr.read() {
+        let Ok(mut level_map) = level_q.get_mut(ready.parent) else { continue };
+
+        let mut spawn_points: Vec<(Option<usize>, String, Vec3)> = Vec::new();
+
+        for node in children_q.iter_descendants(ready.parent) {
+            let Ok((name, extras, transform)) = node_q.get(node) else { continue };
+            let name = name.map(|name| name.as_str()).unwrap_or_default();
+
+            let mut markers = match extras {
+                Some(extras) => match serde_json::from_str::<NodeExtras>(&extras.value) {
+                    Ok(markers) => markers,
+                    // the name can still make it a spawn point
+                    Err(e) => {
+                        error!("invalid extras on node {name}: {e}");
+                        NodeExtras::default()
+                    }
+                },
+                None => NodeExtras::default(),
+            };
+
+            if markers.spawn_point.is_none() && name.to_lowercase().starts_with("spawn_point") {
+                markers.spawn_point = Some(serde_json::Value::Bool(true));
+            }
+
+            if let Some(spawn_point) = markers.spawn_point {
+                let index = spawn_point.as_u64().map(|index| index as usize);
+                spawn_points.push((index, name.to_string(), transform.translation()));
+                commands.entity(node).insert(SpawnPoint { index });
+            }
+
+            if let Some(light) = markers.light {
+                let point_light = PointLight {
+                    color: Color::rgb(light.color[0], light.color[1], light.color[2]),
+                    intensity: light.intensity,
+                    range: light.range,
+                    shadows_enabled: light.shadows,
+                    ..Default::default()
+                };
+                commands.entity(node).with_children(|parent| {
+                    parent.spawn((
+                        LightEmitter::new(&point_light, None),
+                        PointLightBundle {
+                            point_light,
+                            ..Default::default()
+                        },
+                    ));
+                });
+            }
+
+            if let Some(trigger) = markers.trigger {
+                let half_extents = Vec3::from(trigger.half_extents);
+                commands.entity(node).insert((
+                    LevelTrigger { id: trigger.id, half_extents },
+                    Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                    Sensor,
+                    colliders::trigger_groups(),
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+                // the trigger cube is only an editor helper
+                commands.entity(node).insert((Visibility::Hidden, ColliderHint::None));
+            }
+
+            // the colliders are built afterwards, the hint is picked up there
+            if let Some(hint) = markers.collider {
+                commands.entity(node).insert(hint);
+            }
+        }
+
+        // numbered spawn points first, the rest sorted by name so every peer gets the same order
+        spawn_points.sort_by(|a, b| match (a.0, b.0) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.1.cmp(&b.1),
+        });
+        if !spawn_points.is_empty() {
+            info!("level scene has {} spawn points", spawn_points.len());
+            commands.insert_resource(SceneSpawnPoints(spawn_points.into_iter().map(|(_, _, p)| p).collect()));
+        }
+
+        level_map.markers_parsed = true;
+    }
+}
@@ -1,10 +1,222 @@
 use bevy::{
-    prelude::*, utils::HashMap,
+    gltf::Gltf, prelude::*, utils::HashMap,
 };
+use bevy::asset::LoadState;
+use bevy::{render::{mesh::VertexAttributeValues, primitives::Aabb}, scene::SceneInstanceReady};
+use bevy_ggrs::*;
 use bevy_rapier3d::{prelude::*, rapier::geometry::ColliderShape};
+use iyes_progress::{Progress, ProgressSystem};
 
-use crate::game::GameResources;
+use crate::{ui::loading::{LoadingError, TrackedLoading}, AppState, Cubemap};
 
+use crate::player::interaction::Interactable;
+
+use self::{doors::{Door, DoorHinge}, lights::{LightBudget, LightEmitter}, time_of_day::TimeOfDay, triggers::{FiredTriggerActions, TriggerEffect, TriggerOccupants}};
+
+use self::{
+    level::{Level, LevelLoader, SceneCollider},
+    colliders::{ColliderHint, SceneColliders},
+    markers::SceneSpawnPoints,
+    registry::{find_map, MapOffer, MapRotation, MapSource},
+};
+
+pub mod colliders;
+pub mod doors;
+pub mod environment;
+pub mod generator;
+pub mod level;
+pub mod lights;
+pub mod markers;
+pub mod props;
+pub mod registry;
+pub mod time_of_day;
+pub mod triggers;
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .insert_resource(MapRotation::from_args())
+            .init_resource::<MapGenerationColliderStatus>()
+            .init_resource::<FiredTriggerActions>()
+            .init_resource::<LightBudget>()
+            .init_resource::<TimeOfDay>()
+            .add_event::<TriggerEffect>()
+            .rollback_component_with_clone::<TriggerOccupants>()
+            .rollback_component_with_clone::<Door>()
+            .add_systems(OnExit(AppState::InGame), unload_level)
+            // a match which is left while loading has no `InGame` to exit
+            .add_systems(OnEnter(AppState::MainMenu), unload_level)
+            .add_systems(Update, (
+                load_agreed_level.run_if(resource_added::<AgreedMap>()),
+                activate_loaded_level.run_if(resource_exists::<LoadingLevel>()),
+                setup.run_if(resource_added::<ActiveLevel>()),
+                markers::parse_level_markers,
+                recenter_far_meshes,
+                // the hints and triggers of the markers have to exist for the same ready event
+                apply_deferred,
+                colliders::generate_scene_colliders,
+                triggers::attach_scene_trigger_actions,
+                level_ready.track_progress(),
+                colliders::colliders_ready.track_progress(),
+            ).chain().run_if(in_state(AppState::GameLoading)))
+            .add_systems(GgrsSchedule, (
+                triggers::detect_triggers,
+                triggers::apply_trigger_actions,
+                doors::swing_doors,
+            ).chain().after(crate::game::tick_match_clock).run_if(in_state(AppState::InGame)))
+            .add_systems(Update, (
+                environment::prepare_cubemap.run_if(resource_exists::<Cubemap>()),
+                environment::sync_camera_environment,
+            ).chain().run_if(in_state(AppState::GameLoading).or_else(in_state(AppState::InGame))))
+            .add_systems(Update, (
+                triggers::play_trigger_effects,
+                time_of_day::update_time_of_day,
+                (time_of_day::update_sun, time_of_day::update_sky),
+                lights::update_light_emitters,
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+/// The map of the host, inserted as soon as every peer has joined.
+#[derive(Resource, Debug, Clone)]
+pub struct AgreedMap(pub MapOffer);
+
+/// the level file of the agreed map while it is loading
+#[derive(Resource)]
+struct LoadingLevel(Handle<Level>);
+
+/// The level which is played, the map entities are spawned when this is inserted.
+#[derive(Resource)]
+pub struct ActiveLevel(pub Handle<Level>);
+
+fn load_agreed_level(
+    mut commands: Commands,
+    agreed: Res<AgreedMap>,
+    asset_server: Res<AssetServer>,
+    mut levels: ResMut<Assets<Level>>,
+) {
+    let Some(map) = find_map(&agreed.0.map) else {
+        error!("the host plays {}, which is not known here", agreed.0.map);
+        commands.insert_resource(LoadingError(format!("the host plays the map {}, which is not known here", agreed.0.map)));
+        return;
+    };
+
+    info!("next map: {}", map.title);
+    match map.source {
+        MapSource::File(path) => commands.insert_resource(LoadingLevel(asset_server.load(path))),
+        MapSource::Generated => {
+            info!("generating graveyard from seed {}", agreed.0.seed);
+            let level = levels.add(generator::generate_graveyard(agreed.0.seed));
+            commands.insert_resource(ActiveLevel(level));
+        }
+    }
+}
+
+fn activate_loaded_level(
+    mut commands: Commands,
+    loading: Res<LoadingLevel>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+) {
+    if levels.contains(&loading.0) {
+        commands.insert_resource(ActiveLevel(loading.0.clone()));
+        commands.remove_resource::<LoadingLevel>();
+    } else if asset_server.load_state(&loading.0) == LoadState::Failed {
+        error!("could not load the level {:?}", loading.0.path());
+        commands.insert_resource(LoadingError(format!("the level {:?} could not be loaded", loading.0.path())));
+        commands.remove_resource::<LoadingLevel>();
+    }
+}
+
+/// the level is ready when it is spawned and the markers of its scene are read
+fn level_ready(active_level: Option<Res<ActiveLevel>>, level_q: Query<&LevelMap>) -> Progress {
+    (active_level.is_some() && level_q.iter().all(|level_map| level_map.markers_parsed)).into()
+}
+
+/// removes everything of the played level, the next match loads the next map of the rotation
+fn unload_level(
+    mut commands: Commands,
+    active_level: Option<Res<ActiveLevel>>,
+    mut levels: ResMut<Assets<Level>>,
+    mut rotation: ResMut<MapRotation>,
+    mut collider_status: ResMut<MapGenerationColliderStatus>,
+    level_q: Query<Entity, With<LevelEntity>>,
+) {
+    for e in level_q.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    // scenes, meshes and textures of the props go away with the last handle on the entities
+    // a map which was never played stays the next one
+    if let Some(active_level) = active_level {
+        levels.remove(&active_level.0);
+        rotation.advance();
+    }
+    commands.remove_resource::<ActiveLevel>();
+    commands.remove_resource::<AgreedMap>();
+    commands.remove_resource::<LoadingLevel>();
+    commands.remove_resource::<Cubemap>();
+    commands.insert_resource(ClearColor::default());
+    commands.remove_resource::<SceneSpawnPoints>();
+    *collider_status = MapGenerationColliderStatus::default();
+}
+
+/// meshes which are centered farther away from their own origin are moved to it
+const FAR_MESH_DISTANCE: f32 = 256.0;
+
+/// Moving the root of the level by `-origin` leaves the vertex data where it was modeled, so the
+/// meshes of the level scene which are far from their origin are copied with their vertices moved
+/// close to zero. The mesh of the glTF stays as it is, it is cached for the next time the map is
+/// played and may be used elsewhere. The transforms of the entities get the difference, so nothing
+/// moves in the world and the colliders, which are built afterwards, see the new vertices.
+fn recenter_far_meshes(
+    mut commands: Commands,
+    mut ready_evr: EventReader<SceneInstanceReady>,
+    level_q: Query<(), With<LevelMap>>,
+    children_q: Query<&Children>,
+    mut mesh_q: Query<(Entity, &Handle<Mesh>, &mut Transform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for ready in ready_evr.read() {
+        if level_q.get(ready.parent).is_err() {
+            continue;
+        }
+
+        // the moved copy and the offset, `None` for meshes which are close enough
+        let mut recentered: HashMap<AssetId<Mesh>, Option<(Handle<Mesh>, Vec3)>> = HashMap::new();
+        for primitive in children_q.iter_descendants(ready.parent) {
+            let Ok((e, handle, mut transform)) = mesh_q.get_mut(primitive) else { continue };
+
+            let copy = recentered.entry(handle.id()).or_insert_with(|| {
+                let mut mesh = meshes.get(handle)?.clone();
+                let center = Vec3::from(mesh.compute_aabb()?.center);
+                if center.length() < FAR_MESH_DISTANCE {
+                    return None;
+                }
+                let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) else {
+                    return None;
+                };
+                for position in positions.iter_mut() {
+                    position[0] -= center.x;
+                    position[1] -= center.y;
+                    position[2] -= center.z;
+                }
+                Some((meshes.add(mesh), center))
+            });
+            let Some((copy, center)) = copy.clone() else { continue };
+
+            transform.translation += transform.rotation * (transform.scale * center);
+            // the bounds for culling are computed again from the moved vertices
+            commands.entity(e).insert(copy).remove::<Aabb>();
+        }
+    }
+}
+
+/// all colliders of the level exist, tracked as loading progress
 #[derive(Resource, Default)]
 pub struct MapGenerationColliderStatus {
     pub already_generated: bool,
@@ -13,136 +225,182 @@ pub struct MapGenerationColliderStatus {
 
 #[derive(Component, Default)]
 pub struct LevelMap {
-    pub colliders_generated: bool
+    pub colliders_generated: bool,
+    /// spawn points, lights and triggers of the scene nodes are spawned
+    pub markers_parsed: bool,
 }
 
+/// Everything which is spawned for the current level, so it can be removed again.
+#[derive(Component)]
+pub struct LevelEntity;
+
 pub fn setup(
-    mut commands: Commands, 
-    game_assets: Res<GameResources>,
-    //mut materials: ResMut<Assets<StandardMaterial>>,
-    //mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+    active_level: Res<ActiveLevel>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut loading: TrackedLoading,
 ) {
+    let Some(level) = levels.get(&active_level.0) else {
+        error!("level is not loaded, can't spawn the map");
+        return;
+    };
 
+    info!("spawn level: {}", level.name);
 
+    if let Some(scene) = &level.scene {
+        // used for every mesh which has no own hint in its name or extras
+        let default = match scene.collider {
+            SceneCollider::TriMesh => ColliderHint::Trimesh,
+            SceneCollider::ConvexHull => ColliderHint::Hull,
+            SceneCollider::None => ColliderHint::None,
+        };
 
-    //let m = &meshes.get(&mesh).unwrap();
-    commands.spawn((
-        //RigidBody::Fixed, 
-        SceneBundle {
-            scene: game_assets.map.clone(),
-            transform: Transform::from_xyz(18.0, 0.0, -2394.0).with_scale(Vec3::new(2.0, 2.0, 2.0)),
-            ..Default::default()
-        }, 
-        AsyncSceneCollider {
-            shape: Some(ComputedColliderShape::TriMesh),
-            ..Default::default()
+        // the match starts when the geometry is there
+        let scene_handle: Handle<Scene> = asset_server.load(&scene.path);
+        loading.add(&scene_handle);
+        if let Some((file, _)) = scene.path.split_once('#') {
+            let gltf: Handle<Gltf> = asset_server.load(file.to_string());
+            loading.add(&gltf);
         }
-    ));
-    commands.spawn(
-        (
-            //RigidBody::Fixed,
-            //Collider::halfspace(Vec3::new(0.0, 1.0, 0.0)).unwrap(),
-            Collider::cuboid(1.0, 0.01, 1.0),
-            TransformBundle {
-                local: Transform::from_xyz(6.0, 2013.0, 12.0),
-                ..Default::default()
-            },
-        )
-    );
-    commands.spawn(
-        (
-            //RigidBody::Fixed,
-            //Collider::halfspace(Vec3::new(0.0, 1.0, 0.0)).unwrap(),
-            Collider::cuboid(1.0, 0.01, 1.0),
-            TransformBundle {
-                local: Transform::from_xyz(4.0, 2013.0, 11.0),
-                ..Default::default()
-            },
-        )
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(26.0, 2012.57, 3.723),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 100.0,
-                color: Color::YELLOW,
+
+        commands.spawn((
+            Name::new(level.name.clone()),
+            SceneBundle {
+                scene: scene_handle,
+                transform: (&scene.transform).into(),
                 ..Default::default()
             },
-            ..Default::default()
-        }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(-7.0, 2015.7, 29.9),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 100.0,
-                color: Color::YELLOW,
-                ..Default::default()
+            SceneColliders {
+                default,
+                groups: colliders::world_groups(),
             },
-            ..Default::default()
-        }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(-34.718, 2017.0, 38.428),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 100.0,
-                color: Color::YELLOW,
+            LevelMap::default(),
+            LevelEntity,
+        ));
+    }
+
+    if let Some(ground) = &level.ground {
+        commands.spawn((
+            Name::new("Ground"),
+            PbrBundle {
+                mesh: meshes.add(shape::Plane::default().into()),
+                material: materials.add(Color::rgb(ground.color[0], ground.color[1], ground.color[2]).into()),
+                transform: Transform::from_xyz(0.0, ground.height, 0.0)
+                    .with_scale(Vec3::new(ground.size.x, 1.0, ground.size.y)),
                 ..Default::default()
             },
-            ..Default::default()
+            LevelEntity,
+        )).with_children(|parent| {
+            // the plane has a size of 1, the collider is scaled together with it
+            parent.spawn((
+                Collider::cuboid(0.5, 0.05, 0.5),
+                colliders::world_groups(),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, -0.05, 0.0)),
+            ));
+        });
+    }
+
+    // every peer spawns the props in the same order, so the index is the same everywhere
+    let mut interactables = 0u8;
+    for prop in level.props.iter() {
+        let mut transform: Transform = (&prop.transform).into();
+        transform.scale *= level.prop_scale;
+
+        // doors swing around their hinge, the model is a child of it
+        let door_hinge = doors::door_hinge(&prop.model).map(|hinge| {
+            let index = interactables;
+            interactables = interactables.checked_add(1).unwrap_or_else(|| {
+                warn!("too many interactables, {} can't be used", prop.model);
+                u8::MAX
+            });
+            let id = prop.id.clone().unwrap_or_else(|| format!("door_{index}"));
+            let closed = transform.rotation;
+            let translation = transform.translation + closed * (hinge * transform.scale);
+            transform = Transform::from_translation(-hinge * transform.scale).with_scale(transform.scale);
+
+            let mut hinge_cmd = commands.spawn((
+                Name::new(id.clone()),
+                Door::new(id),
+                DoorHinge { closed },
+                RigidBody::KinematicPositionBased,
+                TransformBundle::from_transform(Transform::from_translation(translation).with_rotation(closed)),
+                VisibilityBundle::default(),
+                LevelEntity,
+            ));
+            if index < u8::MAX {
+                hinge_cmd.insert(Interactable { index });
+            }
+            hinge_cmd.add_rollback().id()
+        });
+
+        let e = props::spawn_prop(&mut commands, &asset_server, &prop.model, transform);
+        let gltf: Handle<Gltf> = asset_server.load(prop.model.clone());
+        loading.add(&gltf);
+        match door_hinge {
+            Some(hinge) => {
+                commands.entity(e).set_parent(hinge);
+            }
+            None => {
+                commands.entity(e).insert(LevelEntity);
+            }
         }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(-35.0, 2019.8, 38.7),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 50.0,
-                color: Color::YELLOW,
+    }
+
+    for collider in level.colliders.iter() {
+        commands.spawn((
+            collider.shape.collider(),
+            colliders::world_groups(),
+            TransformBundle {
+                local: (&collider.transform).into(),
                 ..Default::default()
             },
-            ..Default::default()
-        }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(32.084, 2016.0, 56.18),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 50.0,
-                color: Color::YELLOW,
+            LevelEntity,
+        ));
+    }
+
+    for light in level.lights.iter() {
+        let point_light = light.point_light();
+        let mut cmd = commands.spawn((
+            LightEmitter::new(&point_light, None),
+            PointLightBundle {
+                transform: Transform::from_translation(light.position),
+                point_light,
                 ..Default::default()
             },
-            ..Default::default()
+            LevelEntity,
+        ));
+        if let Some(name) = &light.name {
+            cmd.insert(Name::new(name.clone()));
         }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(52.575, 2010.874, 66.183),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 50.0,
-                color: Color::YELLOW,
-                ..Default::default()
-            },
-            ..Default::default()
+    }
+
+    triggers::spawn_level_triggers(&mut commands, level);
+
+    let [r, g, b] = level.sky_color;
+    commands.insert_resource(ClearColor(Color::rgb(r, g, b)));
+
+    if let Some(skybox) = &level.skybox {
+        let mut images: Vec<Handle<Image>> = vec![asset_server.load(skybox)];
+        images.extend(level.night_skybox.iter().map(|night| asset_server.load(night)));
+        let environment_map: Option<(Handle<Image>, Handle<Image>)> = level
+            .environment_map
+            .as_ref()
+            .map(|map| (asset_server.load(&map.diffuse), asset_server.load(&map.specular)));
+        for image in images.iter() {
+            loading.add(image);
         }
-    );
-    commands.spawn(
-        PointLightBundle {
-            transform: Transform::from_xyz(-11.341, 2015.874, 41.377),
-            point_light: PointLight {
-                intensity: 50.0,
-                range: 50.0,
-                color: Color::YELLOW,
-                ..Default::default()
-            },
-            ..Default::default()
+        if let Some((diffuse, specular)) = &environment_map {
+            loading.add(diffuse);
+            loading.add(specular);
         }
-    );
-
-}
\ No newline at end of file
+        commands.insert_resource(Cubemap {
+            is_loaded: false,
+            index: 0,
+            images,
+            environment_map,
+        });
+    }
+}
@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    colliders::{self, ColliderHint, SceneColliders},
+    lights::{Flicker, LightEmitter},
+};
+
+/// Collision shape of a prop type, the values are in the unscaled model space of the kit.
+#[derive(Debug, Clone, Copy)]
+pub enum PropCollider {
+    None,
+    Cuboid { min: Vec3, max: Vec3 },
+    /// upright cylinder standing on the ground, e.g. tree trunks
+    Cylinder { radius: f32, height: f32 },
+    /// computed from the meshes of the prop when they are loaded
+    ConvexHull,
+}
+
+/// A light source of a prop, like the flame of a candle.
+#[derive(Debug, Clone, Copy)]
+pub struct PropLight {
+    pub offset: Vec3,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    /// only the closest lights get shadows, see `LightBudget`
+    pub shadows: bool,
+    pub flicker: Option<Flicker>,
+}
+
+impl PropLight {
+    pub fn point_light(&self, scale: f32) -> PointLight {
+        PointLight {
+            intensity: self.intensity * scale,
+            range: self.range * scale,
+            color: Color::rgb(self.color[0], self.color[1], self.color[2]),
+            shadows_enabled: self.shadows,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PropKind {
+    pub collider: PropCollider,
+    pub lights: &'static [PropLight],
+}
+
+impl PropKind {
+    pub const UNKNOWN: PropKind = PropKind {
+        collider: PropCollider::ConvexHull,
+        lights: &[],
+    };
+}
+
+/// Looks up the prop kind by the file name of the model, e.g. `crypt-small.glb`.
+/// Unknown models get a convex hull and no lights.
+pub fn prop_kind(model: &str) -> PropKind {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    let name = name.strip_suffix(".glb").unwrap_or(name);
+
+    PROP_KIT
+        .iter()
+        .find(|(kit_name, _, _)| *kit_name == name)
+        .map(|(_, collider, lights)| PropKind { collider: *collider, lights })
+        .unwrap_or(PropKind::UNKNOWN)
+}
+
+// generated from the bounding boxes of the graveyard kit models
+#[rustfmt::skip]
+const PROP_KIT: &[(&str, PropCollider, &[PropLight])] = &[
+    ("altar-stone", PropCollider::Cuboid { min: Vec3::new(-0.53, 0.00, -0.33), max: Vec3::new(0.53, 0.49, 0.33) }, &[]),
+    ("altar-wood", PropCollider::Cuboid { min: Vec3::new(-0.53, 0.00, -0.33), max: Vec3::new(0.53, 0.47, 0.33) }, &[]),
+    ("bench-damaged", PropCollider::ConvexHull, &[]),
+    ("bench", PropCollider::Cuboid { min: Vec3::new(-0.38, 0.00, -0.19), max: Vec3::new(0.38, 0.55, 0.19) }, &[]),
+    ("border-pillar", PropCollider::Cuboid { min: Vec3::new(-0.05, 0.00, -0.05), max: Vec3::new(0.05, 0.79, 0.05) }, &[]),
+    ("brick-wall-curve-small", PropCollider::ConvexHull, &[]),
+    ("brick-wall-curve", PropCollider::ConvexHull, &[]),
+    ("brick-wall-end", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.51), max: Vec3::new(0.50, 0.82, -0.31) }, &[]),
+    ("brick-wall", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.70, -0.32) }, &[]),
+    ("candle-multiple", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.14, 0.00), color: [1.0, 0.62, 0.28], intensity: 80.0, range: 4.0, shadows: false, flicker: Some(Flicker::CANDLE) },
+    ]),
+    ("candle", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.14, 0.00), color: [1.0, 0.62, 0.28], intensity: 40.0, range: 3.0, shadows: false, flicker: Some(Flicker::CANDLE) },
+    ]),
+    ("coffin-old", PropCollider::Cuboid { min: Vec3::new(-0.30, 0.00, -0.50), max: Vec3::new(0.30, 0.21, 0.50) }, &[]),
+    ("coffin", PropCollider::Cuboid { min: Vec3::new(-0.30, 0.00, -0.50), max: Vec3::new(0.30, 0.27, 0.50) }, &[]),
+    ("column-large", PropCollider::Cylinder { radius: 0.19, height: 1.10 }, &[]),
+    ("cross-column", PropCollider::Cylinder { radius: 0.20, height: 1.42 }, &[]),
+    ("cross-wood", PropCollider::Cuboid { min: Vec3::new(-0.22, 0.00, -0.22), max: Vec3::new(0.22, 1.06, 0.22) }, &[]),
+    ("cross", PropCollider::Cuboid { min: Vec3::new(-0.27, 0.00, -0.06), max: Vec3::new(0.27, 0.99, 0.06) }, &[]),
+    ("crypt-door", PropCollider::Cuboid { min: Vec3::new(0.00, 0.00, -0.05), max: Vec3::new(0.40, 0.80, 0.05) }, &[]),
+    ("crypt-large-roof", PropCollider::None, &[]),
+    ("crypt-large", PropCollider::Cuboid { min: Vec3::new(-0.55, 0.00, -1.05), max: Vec3::new(0.55, 1.00, 1.05) }, &[]),
+    ("crypt-small-entrance-alternative", PropCollider::Cuboid { min: Vec3::new(-0.35, 0.00, -0.10), max: Vec3::new(0.35, 0.95, 0.10) }, &[]),
+    ("crypt-small-entrance", PropCollider::Cuboid { min: Vec3::new(-0.35, 0.00, -0.10), max: Vec3::new(0.35, 0.95, 0.10) }, &[]),
+    ("crypt-small-roof", PropCollider::None, &[]),
+    ("crypt-small", PropCollider::Cuboid { min: Vec3::new(-0.55, 0.00, -0.55), max: Vec3::new(0.55, 1.00, 0.55) }, &[]),
+    ("crypt", PropCollider::Cuboid { min: Vec3::new(-0.38, 0.00, -0.57), max: Vec3::new(0.38, 0.53, 0.57) }, &[]),
+    ("debris-wood", PropCollider::None, &[]),
+    ("debris", PropCollider::None, &[]),
+    ("detail-bowl", PropCollider::None, &[]),
+    ("detail-chalice", PropCollider::None, &[]),
+    ("detail-plate", PropCollider::None, &[]),
+    ("fence-damaged", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.60), max: Vec3::new(0.50, 0.64, -0.20) }, &[]),
+    ("fence-gate", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.56), max: Vec3::new(0.50, 0.64, -0.44) }, &[]),
+    ("fence", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.56), max: Vec3::new(0.50, 0.64, -0.44) }, &[]),
+    ("fire-basket", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.20, 0.00), color: [1.0, 0.45, 0.12], intensity: 400.0, range: 8.0, shadows: true, flicker: Some(Flicker::FIRE) },
+    ]),
+    ("grave-border", PropCollider::None, &[]),
+    ("grave", PropCollider::None, &[]),
+    ("gravestone-bevel", PropCollider::Cuboid { min: Vec3::new(-0.22, 0.00, -0.10), max: Vec3::new(0.22, 0.58, 0.10) }, &[]),
+    ("gravestone-broken", PropCollider::ConvexHull, &[]),
+    ("gravestone-cross-large", PropCollider::Cuboid { min: Vec3::new(-0.22, 0.00, -0.15), max: Vec3::new(0.22, 0.94, 0.15) }, &[]),
+    ("gravestone-cross", PropCollider::Cuboid { min: Vec3::new(-0.22, 0.00, -0.15), max: Vec3::new(0.22, 0.95, 0.15) }, &[]),
+    ("gravestone-debris", PropCollider::ConvexHull, &[]),
+    ("gravestone-decorative", PropCollider::Cuboid { min: Vec3::new(-0.20, 0.00, -0.07), max: Vec3::new(0.20, 0.58, 0.07) }, &[]),
+    ("gravestone-flat-open", PropCollider::Cuboid { min: Vec3::new(-0.24, 0.00, -0.57), max: Vec3::new(0.24, 0.37, 0.57) }, &[]),
+    ("gravestone-flat", PropCollider::Cuboid { min: Vec3::new(-0.24, 0.00, -0.57), max: Vec3::new(0.24, 0.37, 0.57) }, &[]),
+    ("gravestone-roof", PropCollider::Cuboid { min: Vec3::new(-0.20, 0.00, -0.10), max: Vec3::new(0.20, 0.58, 0.10) }, &[]),
+    ("gravestone-round", PropCollider::Cuboid { min: Vec3::new(-0.20, 0.00, -0.07), max: Vec3::new(0.20, 0.58, 0.07) }, &[]),
+    ("gravestone-wide", PropCollider::Cuboid { min: Vec3::new(-0.39, 0.00, -0.11), max: Vec3::new(0.39, 0.49, 0.11) }, &[]),
+    ("hay-bale-bundled", PropCollider::Cuboid { min: Vec3::new(-0.35, 0.00, -0.26), max: Vec3::new(0.35, 0.36, 0.26) }, &[]),
+    ("hay-bale", PropCollider::Cuboid { min: Vec3::new(-0.35, 0.00, -0.25), max: Vec3::new(0.35, 0.35, 0.25) }, &[]),
+    ("iron-fence-bar", PropCollider::Cuboid { min: Vec3::new(-0.05, 0.00, -0.05), max: Vec3::new(0.05, 0.73, 0.05) }, &[]),
+    ("iron-fence-border-column", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.82, -0.40) }, &[]),
+    ("iron-fence-border-curve", PropCollider::ConvexHull, &[]),
+    ("iron-fence-border-gate", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.82, -0.40) }, &[]),
+    ("iron-fence-border", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.82, -0.40) }, &[]),
+    ("iron-fence-curve", PropCollider::ConvexHull, &[]),
+    ("iron-fence-damaged", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.84, -0.40) }, &[]),
+    ("iron-fence", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.82, -0.40) }, &[]),
+    ("lantern-candle", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.15, 0.00), color: [1.0, 0.62, 0.28], intensity: 60.0, range: 5.0, shadows: false, flicker: Some(Flicker::LANTERN) },
+    ]),
+    ("lantern-glass", PropCollider::None, &[]),
+    ("lightpost-all", PropCollider::Cuboid { min: Vec3::new(-0.31, 0.00, -0.31), max: Vec3::new(0.31, 1.32, 0.31) }, &[
+        PropLight { offset: Vec3::new(0.27, 1.05, 0.00), color: [1.0, 0.62, 0.28], intensity: 250.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+        PropLight { offset: Vec3::new(-0.27, 1.05, 0.00), color: [1.0, 0.62, 0.28], intensity: 250.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+        PropLight { offset: Vec3::new(0.00, 1.05, 0.27), color: [1.0, 0.62, 0.28], intensity: 250.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+        PropLight { offset: Vec3::new(0.00, 1.05, -0.27), color: [1.0, 0.62, 0.28], intensity: 250.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+    ]),
+    ("lightpost-double", PropCollider::Cuboid { min: Vec3::new(-0.07, 0.00, -0.31), max: Vec3::new(0.07, 1.32, 0.31) }, &[
+        PropLight { offset: Vec3::new(0.00, 1.05, 0.27), color: [1.0, 0.62, 0.28], intensity: 300.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+        PropLight { offset: Vec3::new(0.00, 1.05, -0.27), color: [1.0, 0.62, 0.28], intensity: 300.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+    ]),
+    ("lightpost-single", PropCollider::Cuboid { min: Vec3::new(-0.07, 0.00, -0.07), max: Vec3::new(0.07, 1.32, 0.31) }, &[
+        PropLight { offset: Vec3::new(0.00, 1.05, 0.27), color: [1.0, 0.62, 0.28], intensity: 300.0, range: 10.0, shadows: true, flicker: Some(Flicker::LANTERN) },
+    ]),
+    ("pillar-large", PropCollider::Cuboid { min: Vec3::new(-0.09, 0.00, -0.08), max: Vec3::new(0.09, 0.91, 0.08) }, &[]),
+    ("pillar-obelisk", PropCollider::Cuboid { min: Vec3::new(-0.21, 0.00, -0.21), max: Vec3::new(0.00, 1.05, 0.00) }, &[]),
+    ("pillar-small", PropCollider::Cuboid { min: Vec3::new(-0.10, 0.00, 0.00), max: Vec3::new(0.00, 0.60, 0.10) }, &[]),
+    ("pillar-square", PropCollider::Cuboid { min: Vec3::new(-0.12, 0.00, -0.12), max: Vec3::new(0.12, 1.04, 0.12) }, &[]),
+    ("pine-crooked", PropCollider::Cylinder { radius: 0.20, height: 2.04 }, &[]),
+    ("pine-fall-crooked", PropCollider::Cylinder { radius: 0.20, height: 2.04 }, &[]),
+    ("pine-fall", PropCollider::Cylinder { radius: 0.20, height: 2.29 }, &[]),
+    ("pine", PropCollider::Cylinder { radius: 0.20, height: 2.29 }, &[]),
+    ("pumpkin-carved", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.12, 0.00), color: [1.0, 0.5, 0.1], intensity: 30.0, range: 2.0, shadows: false, flicker: Some(Flicker::CANDLE) },
+    ]),
+    ("pumpkin-tall-carved", PropCollider::None, &[
+        PropLight { offset: Vec3::new(0.00, 0.12, 0.00), color: [1.0, 0.5, 0.1], intensity: 30.0, range: 2.0, shadows: false, flicker: Some(Flicker::CANDLE) },
+    ]),
+    ("pumpkin-tall", PropCollider::None, &[]),
+    ("pumpkin", PropCollider::None, &[]),
+    ("road", PropCollider::None, &[]),
+    ("rocks-tall", PropCollider::ConvexHull, &[]),
+    ("rocks", PropCollider::ConvexHull, &[]),
+    ("shovel-dirt", PropCollider::ConvexHull, &[]),
+    ("shovel", PropCollider::None, &[]),
+    ("stone-wall-column", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.79, -0.40) }, &[]),
+    ("stone-wall-curve", PropCollider::ConvexHull, &[]),
+    ("stone-wall-damaged", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.65, -0.40) }, &[]),
+    ("stone-wall", PropCollider::Cuboid { min: Vec3::new(-0.50, 0.00, -0.50), max: Vec3::new(0.50, 0.65, -0.40) }, &[]),
+    ("trunk-long", PropCollider::ConvexHull, &[]),
+    ("trunk", PropCollider::ConvexHull, &[]),
+    ("urn", PropCollider::None, &[]),
+];
+
+/// Spawns the model of a prop with its collider and lights. The caller decides where it belongs,
+/// props of the level get a `LevelEntity` or are put below a door hinge.
+pub fn spawn_prop(commands: &mut Commands, asset_server: &AssetServer, model: &str, transform: Transform) -> Entity {
+    let kind = prop_kind(model);
+    let scale = transform.scale.max_element();
+
+    let mut cmd = commands.spawn((
+        Name::new(model.to_string()),
+        SceneBundle {
+            scene: asset_server.load(format!("{model}#Scene0")),
+            transform,
+            ..Default::default()
+        },
+    ));
+
+    match kind.collider {
+        PropCollider::None => {}
+        PropCollider::ConvexHull => {
+            cmd.insert(SceneColliders {
+                default: ColliderHint::Hull,
+                groups: colliders::prop_groups(),
+            });
+        }
+        PropCollider::Cuboid { min, max } => {
+            let half_extents = (max - min) / 2.0;
+            cmd.with_children(|parent| {
+                parent.spawn((
+                    Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                    colliders::prop_groups(),
+                    TransformBundle::from_transform(Transform::from_translation((min + max) / 2.0)),
+                ));
+            });
+        }
+        PropCollider::Cylinder { radius, height } => {
+            cmd.with_children(|parent| {
+                parent.spawn((
+                    Collider::cylinder(height / 2.0, radius),
+                    colliders::prop_groups(),
+                    TransformBundle::from_transform(Transform::from_xyz(0.0, height / 2.0, 0.0)),
+                ));
+            });
+        }
+    }
+
+    cmd.with_children(|parent| {
+        for light in kind.lights {
+            let point_light = light.point_light(scale);
+            parent.spawn((
+                LightEmitter::new(&point_light, light.flicker),
+                PointLightBundle {
+                    transform: Transform::from_translation(light.offset),
+                    point_light,
+                    ..Default::default()
+                },
+            ));
+        }
+    });
+
+    cmd.id()
+}
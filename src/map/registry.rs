@@ -0,0 +1,133 @@
+//! All maps which can be played, and the order in which they are played.
+
+use bevy::prelude::*;
+use bevy_matchbox::prelude::PeerId;
+use serde::{Deserialize, Serialize};
+
+pub enum MapSource {
+    /// a level file in `assets/levels`
+    File(&'static str),
+    /// built by the graveyard generator from the agreed seed
+    Generated,
+}
+
+pub struct MapInfo {
+    /// used on the command line and in the lobby
+    pub name: &'static str,
+    pub title: &'static str,
+    pub source: MapSource,
+}
+
+pub const MAPS: &[MapInfo] = &[
+    MapInfo {
+        name: "de_dust2",
+        title: "Dust 2",
+        source: MapSource::File("levels/de_dust2.level.ron"),
+    },
+    MapInfo {
+        name: "graveyard",
+        title: "Graveyard",
+        source: MapSource::File("levels/graveyard.level.ron"),
+    },
+    MapInfo {
+        name: "procedural",
+        title: "Random Graveyard",
+        source: MapSource::Generated,
+    },
+];
+
+pub fn find_map(name: &str) -> Option<&'static MapInfo> {
+    MAPS.iter().find(|map| map.name == name)
+}
+
+/// What a peer wants to play, only the offer of the host (first player) counts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapOffer {
+    pub map: String,
+    /// used if the map is generated
+    pub seed: u64,
+}
+
+/// The maps which are played one after another.
+/// `--map <name>` plays only one map, `--rotation a,b,c` a list, `--seed <n>` fixes the seed for generated maps.
+#[derive(Resource, Clone)]
+pub struct MapRotation {
+    pub maps: Vec<String>,
+    pub current: usize,
+    pub seed: Option<u64>,
+}
+
+impl Default for MapRotation {
+    fn default() -> Self {
+        Self {
+            maps: MAPS.iter().map(|map| map.name.to_string()).collect(),
+            current: 0,
+            seed: None,
+        }
+    }
+}
+
+impl MapRotation {
+    pub fn from_args() -> Self {
+        let mut rotation = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--map" => {
+                    if let Some(name) = args.next() {
+                        rotation.maps = vec![name];
+                    }
+                }
+                "--rotation" => {
+                    if let Some(names) = args.next() {
+                        rotation.maps = names.split(',').map(|name| name.trim().to_string()).collect();
+                    }
+                }
+                "--seed" => rotation.seed = args.next().and_then(|seed| seed.parse().ok()),
+                _ => {}
+            }
+        }
+
+        rotation.maps.retain(|name| {
+            let known = find_map(name).is_some();
+            if !known {
+                warn!("unknown map {name}, it is not part of the rotation");
+            }
+            known
+        });
+        if rotation.maps.is_empty() {
+            rotation.maps = Self::default().maps;
+        }
+        rotation
+    }
+
+    pub fn current(&self) -> &str {
+        &self.maps[self.current % self.maps.len()]
+    }
+
+    /// called after each match
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.maps.len();
+        // a fixed seed would give the same graveyard again
+        self.seed = None;
+    }
+
+    /// the map is played after the current one, the rotation goes on from there
+    pub fn play_next(&mut self, name: &str) {
+        let next = self.current % self.maps.len() + 1;
+        self.maps.insert(next, name.to_string());
+    }
+
+    /// the offer this peer sends in the lobby, the peer id is random enough if no seed was given
+    pub fn offer(&self, local: PeerId) -> MapOffer {
+        self.offer_with_seed(local.0.as_u128() as u64 ^ self.current as u64)
+    }
+
+    /// the seed is used if none was given on the command line
+    pub fn offer_with_seed(&self, seed: u64) -> MapOffer {
+        MapOffer {
+            map: self.current().to_string(),
+            seed: self.seed.unwrap_or(seed),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! The time of day moves the sun, colours the light and switches the sky and the lights of the map.
+//!
+//! It is derived from the `MatchClock`, which is part of the rollback state, so every peer has the
+//! same time without sending anything.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{game::MatchClock, Cubemap};
+
+use super::{level::Level, ActiveLevel};
+
+/// the sun at noon
+const SUN_ILLUMINANCE: f32 = 500.0;
+/// the moon when it is at its highest
+const MOON_ILLUMINANCE: f32 = 25.0;
+
+/// the directional light which is moved by the time of day
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct TimeOfDay {
+    /// 0 to 24
+    pub hour: f32,
+    /// false if the level has a fixed time, its lights are always on then
+    pub running: bool,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 14.0,
+            running: false,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// 0 at sunrise (6:00), PI at sunset (18:00)
+    fn sun_angle(&self) -> f32 {
+        (self.hour - 6.0) / 12.0 * PI
+    }
+
+    /// 1 at noon, 0 from sunset to sunrise
+    pub fn daylight(&self) -> f32 {
+        self.sun_angle().sin().max(0.0)
+    }
+
+    /// the map lights are switched on at dusk, each at a slightly different time
+    pub fn lights_on(&self, jitter: f32) -> bool {
+        !self.running || self.daylight() < 0.15 + 0.1 * jitter
+    }
+}
+
+pub fn update_time_of_day(
+    clock: Res<MatchClock>,
+    active_level: Option<Res<ActiveLevel>>,
+    levels: Res<Assets<Level>>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    let Some(level) = active_level.and_then(|active_level| levels.get(&active_level.0)) else { return };
+    let cycle = level.day_cycle;
+
+    let hour = if cycle.day_length > 0.0 {
+        (cycle.start_hour + clock.seconds() / cycle.day_length * 24.0).rem_euclid(24.0)
+    } else {
+        cycle.start_hour
+    };
+    let running = cycle.day_length > 0.0;
+    if time_of_day.hour != hour || time_of_day.running != running {
+        time_of_day.hour = hour;
+        time_of_day.running = running;
+    }
+}
+
+pub fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    mut ambient: ResMut<AmbientLight>,
+    mut sun_q: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    let angle = time_of_day.sun_angle();
+    // the sun goes from east to west, a bit to the south
+    let sun = Vec3::new(angle.cos(), angle.sin(), 0.35).normalize();
+    let daylight = time_of_day.daylight();
+
+    // the moon is opposite of the sun
+    let (direction, color, illuminance) = if sun.y > 0.0 {
+        let color = Color::rgb(1.0, 0.5, 0.25).lerp(Color::rgb(1.0, 0.97, 0.9), daylight.sqrt());
+        (sun, color, SUN_ILLUMINANCE * daylight.sqrt())
+    } else {
+        (-sun, Color::rgb(0.6, 0.7, 1.0), MOON_ILLUMINANCE * -sun.y)
+    };
+
+    for (mut light, mut transform) in sun_q.iter_mut() {
+        light.color = color;
+        light.illuminance = illuminance;
+        *transform = Transform::default().looking_to(-direction, Vec3::Y);
+    }
+
+    ambient.color = Color::rgb(0.3, 0.35, 0.6).lerp(Color::WHITE, daylight);
+    ambient.brightness = 0.03 + 0.12 * daylight;
+}
+
+/// the night sky is shown when the sun is down if the level has one, `environment` puts it on the camera
+pub fn update_sky(time_of_day: Res<TimeOfDay>, cubemap: Option<ResMut<Cubemap>>) {
+    let Some(mut cubemap) = cubemap else { return };
+    let index = if time_of_day.daylight() > 0.05 { 0 } else { 1 };
+    if cubemap.index != index && index < cubemap.images.len() {
+        cubemap.index = index;
+    }
+}
+
+trait ColorLerp {
+    fn lerp(self, other: Color, t: f32) -> Color;
+}
+
+impl ColorLerp for Color {
+    fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        Color::rgb(
+            self.r() + (other.r() - self.r()) * t,
+            self.g() + (other.g() - self.g()) * t,
+            self.b() + (other.b() - self.b()) * t,
+        )
+    }
+}
@@ -0,0 +1,257 @@
+//! Trigger volumes fire enter, exit and stay events for everything with a `TriggerActivator`.
+//!
+//! The overlap test and the gameplay actions run in the `GgrsSchedule`, so they are predicted and
+//! rolled back like the rest of the simulation. Sounds and lights are only local effects, they are
+//! sent as `TriggerEffect` events and played once, even if the frame is simulated again.
+
+use bevy::prelude::*;
+use bevy_ggrs::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    game::{MatchClock, GGRS_FPS},
+    player::PlayerStats,
+    settings::Settings,
+};
+
+use super::{
+    colliders,
+    doors::Door,
+    level::{Level, TriggerAction, TriggerDescription},
+    lights::LightEmitter,
+    markers::LevelTrigger,
+    ActiveLevel, LevelEntity,
+};
+
+/// players (and later npcs) which can set off triggers
+#[derive(Component)]
+pub struct TriggerActivator;
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct TriggerActions {
+    pub on_enter: Vec<TriggerAction>,
+    pub on_exit: Vec<TriggerAction>,
+    pub on_stay: Vec<TriggerAction>,
+}
+
+impl From<&TriggerDescription> for TriggerActions {
+    fn from(trigger: &TriggerDescription) -> Self {
+        Self {
+            on_enter: trigger.on_enter.clone(),
+            on_exit: trigger.on_exit.clone(),
+            on_stay: trigger.on_stay.clone(),
+        }
+    }
+}
+
+/// who was inside in the last frame, rolled back together with the players
+#[derive(Component, Clone, Debug, Default)]
+pub struct TriggerOccupants(pub Vec<Entity>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerPhase {
+    Enter,
+    Exit,
+    Stay,
+}
+
+/// a local effect of a trigger, see the module docs
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TriggerEffect {
+    pub trigger: Entity,
+    pub frame: u32,
+    pub action: TriggerAction,
+}
+
+/// the actions which fired in this frame, only used between detection and execution
+#[derive(Resource, Default)]
+pub struct FiredTriggerActions(Vec<(Entity, Entity, TriggerPhase, TriggerAction)>);
+
+/// spawns the triggers which have their volume in the level file
+pub fn spawn_level_triggers(commands: &mut Commands, level: &Level) {
+    for trigger in level.triggers.iter() {
+        let Some(volume) = &trigger.volume else { continue };
+        let half_extents = volume.half_extents;
+
+        commands
+            .spawn((
+                Name::new(format!("trigger {}", trigger.id)),
+                LevelTrigger {
+                    id: trigger.id.clone(),
+                    half_extents,
+                },
+                TriggerActions::from(trigger),
+                TriggerOccupants::default(),
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                Sensor,
+                colliders::trigger_groups(),
+                TransformBundle::from_transform((&volume.transform).into()),
+                LevelEntity,
+            ))
+            .add_rollback();
+    }
+}
+
+/// triggers of the level scene only have an id, their actions come from the level file
+pub fn attach_scene_trigger_actions(
+    mut commands: Commands,
+    active_level: Option<Res<ActiveLevel>>,
+    levels: Res<Assets<Level>>,
+    trigger_q: Query<(Entity, &LevelTrigger), Without<TriggerActions>>,
+) {
+    let Some(level) = active_level.and_then(|active_level| levels.get(&active_level.0)) else { return };
+
+    for (e, trigger) in trigger_q.iter() {
+        match level.triggers.iter().find(|t| t.id == trigger.id) {
+            Some(description) => {
+                commands
+                    .entity(e)
+                    .insert((TriggerActions::from(description), TriggerOccupants::default()))
+                    .add_rollback();
+            }
+            None => {
+                warn!("trigger {} of the level scene has no actions", trigger.id);
+                commands.entity(e).insert(TriggerActions::default());
+            }
+        }
+    }
+}
+
+/// a point in the (rotated and scaled) box of the trigger
+fn contains(trigger: &LevelTrigger, transform: &GlobalTransform, point: Vec3) -> bool {
+    let local = transform.affine().inverse().transform_point3(point);
+    local.abs().cmple(trigger.half_extents).all()
+}
+
+pub fn detect_triggers(
+    mut trigger_q: Query<(Entity, &LevelTrigger, &GlobalTransform, &TriggerActions, &mut TriggerOccupants)>,
+    activator_q: Query<(Entity, &Transform), With<TriggerActivator>>,
+    mut fired: ResMut<FiredTriggerActions>,
+) {
+    fired.0.clear();
+
+    for (trigger_entity, trigger, transform, actions, mut occupants) in trigger_q.iter_mut() {
+        let inside: Vec<Entity> = activator_q
+            .iter()
+            .filter(|(_, activator)| contains(trigger, transform, activator.translation))
+            .map(|(e, _)| e)
+            .collect();
+
+        let mut fire = |activator: Entity, phase: TriggerPhase, list: &[TriggerAction]| {
+            for action in list {
+                fired.0.push((trigger_entity, activator, phase, action.clone()));
+            }
+        };
+
+        for activator in inside.iter() {
+            if occupants.0.contains(activator) {
+                fire(*activator, TriggerPhase::Stay, &actions.on_stay);
+            } else {
+                fire(*activator, TriggerPhase::Enter, &actions.on_enter);
+            }
+        }
+        for activator in occupants.0.iter() {
+            if !inside.contains(activator) {
+                fire(*activator, TriggerPhase::Exit, &actions.on_exit);
+            }
+        }
+
+        if occupants.0 != inside {
+            occupants.0 = inside;
+        }
+    }
+}
+
+pub fn apply_trigger_actions(
+    mut fired: ResMut<FiredTriggerActions>,
+    mut clock: ResMut<MatchClock>,
+    mut activator_q: Query<(&mut Transform, Option<&mut PlayerStats>, Option<&mut Velocity>), With<TriggerActivator>>,
+    mut door_q: Query<&mut Door>,
+    mut effects: EventWriter<TriggerEffect>,
+) {
+    for (trigger, activator, phase, action) in fired.0.drain(..) {
+        match action {
+            TriggerAction::PlaySound { .. } | TriggerAction::ToggleLight { .. } => {
+                effects.send(TriggerEffect {
+                    trigger,
+                    frame: clock.frame,
+                    action,
+                });
+            }
+            TriggerAction::OpenDoor { door } => {
+                for mut d in door_q.iter_mut().filter(|d| d.id == door) {
+                    d.open = true;
+                }
+            }
+            TriggerAction::StartRound => {
+                clock.round += 1;
+                clock.round_start = clock.frame;
+                info!("round {} starts", clock.round);
+            }
+            TriggerAction::Teleport { to } => {
+                let Ok((mut transform, _, velocity)) = activator_q.get_mut(activator) else { continue };
+                transform.translation = to;
+                if let Some(mut velocity) = velocity {
+                    *velocity = Velocity::zero();
+                }
+            }
+            TriggerAction::Damage { amount } => {
+                let Ok((_, Some(mut stats), _)) = activator_q.get_mut(activator) else { continue };
+                let amount = if phase == TriggerPhase::Stay {
+                    amount / GGRS_FPS as f32
+                } else {
+                    amount
+                };
+                stats.damage(amount);
+            }
+        }
+    }
+}
+
+/// plays sounds and switches lights, a frame which is simulated again doesn't play them twice
+pub fn play_trigger_effects(
+    mut commands: Commands,
+    mut effects: EventReader<TriggerEffect>,
+    mut played: Local<Vec<TriggerEffect>>,
+    clock: Res<MatchClock>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    trigger_q: Query<&GlobalTransform>,
+    mut light_q: Query<(&Name, &mut LightEmitter)>,
+) {
+    // effects of frames which are older than the rollback window can't come again
+    played.retain(|effect| effect.frame + GGRS_FPS as u32 * 2 > clock.frame);
+
+    for effect in effects.read() {
+        if played.contains(effect) {
+            continue;
+        }
+        played.push(effect.clone());
+
+        match &effect.action {
+            TriggerAction::PlaySound { path } => {
+                let position = trigger_q.get(effect.trigger).map(|t| t.translation()).unwrap_or_default();
+                commands.spawn((
+                    AudioBundle {
+                        source: asset_server.load(path),
+                        settings: PlaybackSettings {
+                            mode: bevy::audio::PlaybackMode::Despawn,
+                            volume: settings.effects(),
+                            spatial: true,
+                            ..default()
+                        },
+                        ..Default::default()
+                    },
+                    TransformBundle::from_transform(Transform::from_translation(position)),
+                ));
+            }
+            TriggerAction::ToggleLight { target } => {
+                for (name, mut emitter) in light_q.iter_mut().filter(|(name, _)| name.as_str() == target) {
+                    debug!("toggle light {name}");
+                    emitter.on = !emitter.on;
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Cheats of the developer console, only allowed in the offline synctest session.
+//!
+//! A cheat is scheduled for a frame of the `MatchClock` and applied in the `GgrsSchedule`, so it is
+//! applied again when that frame is simulated again after a rollback. The state of the cheats is a
+//! rollback component, like everything else the simulation reads.
+
+use bevy::prelude::*;
+use bevy_ggrs::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    game::{Config, MatchClock, GGRS_FPS, INPUT_CROUCH, INPUT_JUMP, INPUT_RUN},
+    map::{props, LevelEntity},
+};
+
+use super::{Player, PlayerStats};
+
+/// flying speed with noclip, running is three times as fast
+const NOCLIP_SPEED: f32 = 8.0;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct DevCheats {
+    pub god: bool,
+    pub noclip: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vital {
+    Health,
+    Stamina,
+    Mana,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cheat {
+    Teleport(Vec3),
+    ToggleGod,
+    ToggleNoclip,
+    Give(Vital, f32),
+    /// a prop of the level kit, it is a rollback entity so it goes away with the frame it came from
+    Spawn { model: String, transform: Transform },
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledCheat {
+    pub frame: u32,
+    pub handle: usize,
+    pub cheat: Cheat,
+}
+
+/// Not part of the rollback state, the cheats have to stay until their frame can't come again.
+#[derive(Resource, Default)]
+pub struct ScheduledCheats(pub Vec<ScheduledCheat>);
+
+impl ScheduledCheats {
+    /// the cheat is applied in the next simulated frame
+    pub fn schedule(&mut self, clock: &MatchClock, handle: usize, cheat: Cheat) {
+        self.0.push(ScheduledCheat {
+            frame: clock.frame + 1,
+            handle,
+            cheat,
+        });
+    }
+}
+
+pub fn forget_old_cheats(clock: Res<MatchClock>, mut scheduled: ResMut<ScheduledCheats>) {
+    if scheduled.0.is_empty() {
+        return;
+    }
+    scheduled.0.retain(|cheat| cheat.frame + GGRS_FPS as u32 * 2 > clock.frame);
+}
+
+pub fn apply_scheduled_cheats(
+    clock: Res<MatchClock>,
+    scheduled: Res<ScheduledCheats>,
+    mut player_q: Query<(&Player, &mut Transform, &mut Velocity, &mut PlayerStats, &mut DevCheats)>,
+) {
+    for scheduled in scheduled.0.iter().filter(|cheat| cheat.frame == clock.frame) {
+        for (player, mut transform, mut velocity, mut stats, mut cheats) in player_q.iter_mut() {
+            if player.handle != scheduled.handle {
+                continue;
+            }
+            match scheduled.cheat {
+                Cheat::Spawn { .. } => {}
+                Cheat::Teleport(position) => {
+                    transform.translation = position;
+                    *velocity = Velocity::zero();
+                }
+                Cheat::ToggleGod => cheats.god = !cheats.god,
+                Cheat::ToggleNoclip => cheats.noclip = !cheats.noclip,
+                Cheat::Give(vital, amount) => {
+                    let (value, max) = match vital {
+                        Vital::Health => (&mut stats.health, stats.max_health),
+                        Vital::Stamina => (&mut stats.stamina, stats.max_stamina),
+                        Vital::Mana => (&mut stats.mana, stats.max_mana),
+                    };
+                    *value = (*value + amount).min(max);
+                }
+            }
+        }
+    }
+}
+
+pub fn spawn_cheat_props(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    clock: Res<MatchClock>,
+    scheduled: Res<ScheduledCheats>,
+) {
+    for scheduled in scheduled.0.iter().filter(|cheat| cheat.frame == clock.frame) {
+        let Cheat::Spawn { model, transform } = &scheduled.cheat else { continue };
+        let prop = props::spawn_prop(&mut commands, &asset_server, model, *transform);
+        commands.entity(prop).insert(LevelEntity).add_rollback();
+    }
+}
+
+/// god keeps the vitals full, noclip flies where the camera looks, as sent with the input
+pub fn apply_dev_cheats(
+    inputs: Res<PlayerInputs<Config>>,
+    mut player_q: Query<(&Player, &DevCheats, &mut Transform, &mut Velocity, &mut PlayerStats)>,
+) {
+    for (player, cheats, mut transform, mut velocity, mut stats) in player_q.iter_mut() {
+        if cheats.god {
+            stats.health = stats.max_health;
+            stats.stamina = stats.max_stamina;
+            stats.mana = stats.max_mana;
+        }
+
+        if !cheats.noclip {
+            continue;
+        }
+        let (state, _) = inputs[player.handle];
+        let look = state.look();
+
+        let movement = state.movement();
+        let mut direction = look * Vec3::NEG_Z * movement.y + look * Vec3::X * movement.x;
+        if state.input & INPUT_JUMP != 0 {
+            direction += Vec3::Y;
+        }
+        if state.input & INPUT_CROUCH != 0 {
+            direction -= Vec3::Y;
+        }
+        let speed = if state.input & INPUT_RUN != 0 { NOCLIP_SPEED * 3.0 } else { NOCLIP_SPEED };
+
+        transform.translation += direction.clamp_length_max(1.0) * speed / GGRS_FPS as f32;
+        *velocity = Velocity::zero();
+    }
+}
+
+/// without a collider and gravity the body goes through everything, this is local to the rapier world
+pub fn sync_noclip_bodies(
+    mut commands: Commands,
+    player_q: Query<(Entity, &DevCheats, &RigidBody, Has<ColliderDisabled>), Changed<DevCheats>>,
+) {
+    for (e, cheats, body, disabled) in player_q.iter() {
+        if cheats.noclip && (!disabled || *body != RigidBody::KinematicPositionBased) {
+            commands.entity(e).insert((ColliderDisabled, RigidBody::KinematicPositionBased));
+        } else if !cheats.noclip && disabled {
+            commands.entity(e).remove::<ColliderDisabled>().insert(RigidBody::Dynamic);
+        }
+    }
+}
+
+/// the clock starts again with the next match
+pub fn clear_cheats(mut scheduled: ResMut<ScheduledCheats>) {
+    scheduled.0.clear();
+}
@@ -0,0 +1,122 @@
+//! Players use doors (and later other things) by looking at them and pressing the interact button.
+//!
+//! What the local player looks at is found with a ray from the camera, its index is sent with the
+//! input. The simulation only checks that the player is close enough, so every peer does the same.
+
+use bevy::prelude::*;
+use bevy_ggrs::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    camera::ThirdPersonCamera,
+    game::{Config, INPUT_INTERACT},
+    map::{colliders, doors::Door},
+};
+
+use super::{MainPlayer, Player};
+
+/// how far away from the player something can be used
+pub const INTERACT_RANGE: f32 = 2.5;
+
+/// the hinge is at the side of a door, the gates of the kit are up to 3m wide
+const MAX_DOOR_WIDTH: f32 = 3.0;
+
+/// Something a player can use, the index is the same on every peer.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Interactable {
+    pub index: u8,
+}
+
+/// the interact button in the last frame, holding it doesn't toggle a door every frame
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Interactor {
+    pub held: bool,
+}
+
+/// the interactable the local player looks at
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct LookTarget {
+    pub entity: Option<Entity>,
+    pub index: Option<u8>,
+}
+
+pub fn find_look_target(
+    rapier: Res<RapierContext>,
+    camera_q: Query<&GlobalTransform, With<ThirdPersonCamera>>,
+    player_q: Query<&GlobalTransform, With<MainPlayer>>,
+    interactable_q: Query<&Interactable>,
+    parent_q: Query<&Parent>,
+    mut target: ResMut<LookTarget>,
+) {
+    let look_target = match (camera_q.get_single(), player_q.get_single()) {
+        (Ok(camera), Ok(player)) => look_at(&rapier, camera, player.translation(), &interactable_q, &parent_q),
+        _ => LookTarget::default(),
+    };
+
+    if *target != look_target {
+        *target = look_target;
+    }
+}
+
+fn look_at(
+    rapier: &RapierContext,
+    camera: &GlobalTransform,
+    player: Vec3,
+    interactable_q: &Query<&Interactable>,
+    parent_q: &Query<&Parent>,
+) -> LookTarget {
+    let origin = camera.translation();
+    let direction = camera.forward();
+    let max_toi = origin.distance(player) + INTERACT_RANGE;
+
+    let Some((hit, toi)) = rapier.cast_ray(origin, direction, max_toi, true, colliders::interact_filter()) else {
+        return LookTarget::default();
+    };
+    if (origin + direction * toi).distance(player) > INTERACT_RANGE {
+        return LookTarget::default();
+    }
+
+    // the collider is somewhere below the interactable
+    std::iter::once(hit)
+        .chain(parent_q.iter_ancestors(hit))
+        .find_map(|e| {
+            interactable_q.get(e).ok().map(|interactable| LookTarget {
+                entity: Some(e),
+                index: Some(interactable.index),
+            })
+        })
+        .unwrap_or_default()
+}
+
+pub fn use_interactables(
+    inputs: Res<PlayerInputs<Config>>,
+    mut player_q: Query<(&Player, &Transform, &mut Interactor)>,
+    interactable_q: Query<(Entity, &Interactable, &Transform)>,
+    mut door_q: Query<&mut Door>,
+) {
+    for (player, transform, mut interactor) in player_q.iter_mut() {
+        let (state, _) = inputs[player.handle];
+        let pressed = state.input & INPUT_INTERACT != 0;
+        if pressed == interactor.held {
+            continue;
+        }
+        interactor.held = pressed;
+        if !pressed {
+            continue;
+        }
+
+        let Some(index) = state.target.checked_sub(1) else { continue };
+
+        let Some((e, _, target)) = interactable_q.iter().find(|(_, interactable, _)| interactable.index == index) else {
+            continue;
+        };
+        // the input of a peer could name anything, only things in reach are used
+        if target.translation.distance(transform.translation) > INTERACT_RANGE + MAX_DOOR_WIDTH {
+            continue;
+        }
+
+        if let Ok(mut door) = door_q.get_mut(e) {
+            door.open = !door.open;
+        }
+    }
+}
@@ -9,15 +9,18 @@ use bevy_rapier3d::prelude::*;
 use bevy_tnua_rapier3d::{TnuaRapier3dIOBundle, TnuaRapier3dPlugin, TnuaRapier3dSensorShape};
 use std::f32::consts::{FRAC_2_PI, PI};
 
-use crate::{MainCamera, camera::{ThirdPersonCameraTarget, PlayerCamera}, AppState, game::{GameResources, INPUT_UP, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_RUN, Config}};
+use crate::{map::{colliders, level::Level, markers::SceneSpawnPoints, triggers::TriggerActivator, ActiveLevel}, MainCamera, camera::{ThirdPersonCameraTarget, PlayerCamera}, AppState, game::{GameResources, INPUT_RUN, INPUT_JUMP, INPUT_CROUCH, INPUT_DASH, Config}, settings::Settings};
 
 use self::ani_patcher::GltfSceneHandler;
 
 mod ani_patcher;
 mod animations;
+pub mod cheats;
+pub mod interaction;
 mod oponent;
 
-#[derive(Resource)]
+/// per player and part of the rollback state, everything which changes it runs in the `GgrsSchedule`
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
 pub struct PlayerStats {
     pub health: f32,
     pub max_health: f32,
@@ -27,6 +30,80 @@ pub struct PlayerStats {
     pub max_mana: f32,
 }
 
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self {
+            health: 100.0,
+            max_health: 100.0,
+            stamina: 100.0,
+            max_stamina: 100.0,
+            mana: 100.0,
+            max_mana: 100.0,
+        }
+    }
+}
+
+impl PlayerStats {
+    pub fn damage(&mut self, amount: f32) {
+        self.health = (self.health - amount).clamp(0.0, self.max_health);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0.0
+    }
+}
+
+/// how a player crouches, jumps and dashes, the air actions share one counter, see `AirActions`
+#[derive(Component, Clone)]
+pub struct PlayerMotionConfig {
+    pub crouch: TnuaBuiltinCrouch,
+    pub jump: TnuaBuiltinJump,
+    pub dash: TnuaBuiltinDash,
+    pub dash_distance: f32,
+    /// jumps and dashes allowed after leaving the ground
+    pub actions_in_air: usize,
+}
+
+impl Default for PlayerMotionConfig {
+    fn default() -> Self {
+        Self {
+            crouch: TnuaBuiltinCrouch {
+                float_offset: -0.5,
+                ..Default::default()
+            },
+            jump: TnuaBuiltinJump {
+                height: 2.0,
+                ..Default::default()
+            },
+            dash: TnuaBuiltinDash::default(),
+            dash_distance: 4.0,
+            actions_in_air: 1,
+        }
+    }
+}
+
+/// Jumps and dashes started since the player left the ground. The counter of tnua is not part of
+/// the rollback state, this one is and only depends on the inputs.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct AirActions {
+    pub count: usize,
+    /// jump and dash of the last frame, holding the button starts only one action
+    held: u8,
+}
+
+impl AirActions {
+    fn update(&mut self, grounded: bool, input: u8) {
+        let buttons = input & (INPUT_JUMP | INPUT_DASH);
+        let started = buttons & !self.held != 0;
+        self.held = buttons;
+        if grounded {
+            self.count = 0;
+        } else if started {
+            self.count += 1;
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct MainPlayer;
 
@@ -58,15 +135,39 @@ impl Plugin for PlayerPlugin {
                 TnuaRapier3dPlugin,
                 TnuaControllerPlugin,
             ))
-            .add_systems(OnEnter(AppState::GameLoading), (
-                setup_player,
-            ))
+            .rollback_component_with_copy::<PlayerStats>()
+            .checksum_component::<PlayerStats>(|stats| {
+                crate::game::checksum_floats(&[stats.health, stats.stamina, stats.mana], &[])
+            })
+            .rollback_component_with_copy::<interaction::Interactor>()
+            .rollback_component_with_copy::<cheats::DevCheats>()
+            .rollback_component_with_copy::<AirActions>()
+            .init_resource::<interaction::LookTarget>()
+            .init_resource::<cheats::ScheduledCheats>()
+            .init_resource::<ScenePlayersPlaced>()
+            .rollback_resource_with_copy::<ScenePlayersPlaced>()
+            .add_systems(OnEnter(AppState::GameLoading), reset_scene_players_placed)
+            .add_systems(Update, setup_player.run_if(resource_added::<ActiveLevel>()).run_if(in_state(AppState::GameLoading)))
+            // the session already runs while loading, a rollback over the move has to do it again
+            .add_systems(GgrsSchedule, move_players_to_scene_spawn_points.run_if(resource_exists::<SceneSpawnPoints>()))
             .add_systems(GgrsSchedule, (
                     (apply_controls).in_set(TnuaUserControlsSystemSet),
+                    interaction::use_interactables.before(crate::map::doors::swing_doors),
+                    cheats::apply_scheduled_cheats
+                        .after(crate::game::tick_match_clock)
+                        .before(apply_controls),
+                    cheats::spawn_cheat_props.after(crate::game::tick_match_clock),
+                    cheats::apply_dev_cheats
+                        .after(apply_controls)
+                        .after(crate::map::triggers::apply_trigger_actions),
                 ).run_if(in_state(AppState::InGame)
             ))
+            .add_systems(OnExit(AppState::InGame), cheats::clear_cheats)
             .add_systems(Update, (
                 fix_character_rotation,
+                interaction::find_look_target,
+                cheats::forget_old_cheats,
+                cheats::sync_noclip_bodies,
                 setup_camera_to_local_player,
                 ani_patcher::animation_patcher_system,
                 animations::animate,
@@ -228,18 +329,25 @@ fn setup_player_cameras(
 fn setup_player(
     mut commands: Commands, 
     game_assets: Res<GameResources>,
+    active_level: Res<ActiveLevel>,
+    levels: Res<Assets<Level>>,
 ) {
+    let Some(level) = levels.get(&active_level.0) else {
+        error!("level is not loaded, can't spawn the players");
+        return;
+    };
 
     let mut cmd = commands.spawn(Name::new("Player1"));
     cmd.insert(SceneBundle {
         scene: game_assets.player_model.clone(),
-        transform: Transform::from_xyz(6.0, 2020.0, 12.0),
+        transform: Transform::from_translation(level.spawn_point(0)),
         ..Default::default()
     });
     cmd.insert(GltfSceneHandler {
         names_from: game_assets.player.clone(),
     });
     cmd.insert(Collider::capsule_y(0.3, 0.4));
+    cmd.insert(colliders::player_groups());
     cmd.insert(TnuaRapier3dSensorShape(Collider::cylinder(
         0.0, 0.50,
     )));
@@ -247,12 +355,18 @@ fn setup_player(
     cmd.insert(RigidBody::Dynamic);
     cmd.insert(TnuaRapier3dIOBundle::default());
     cmd.insert(TnuaControllerBundle::default());
+    cmd.insert(PlayerMotionConfig::default());
+    cmd.insert(AirActions::default());
     cmd.insert(TnuaAnimatingState::<animations::AnimationState>::default());
     cmd.insert(ThirdPersonCameraTarget);
     cmd.insert(Player {
         handle: 0,
         ..Default::default()
     });
+    cmd.insert(PlayerStats::default());
+    cmd.insert(TriggerActivator);
+    cmd.insert(interaction::Interactor::default());
+    cmd.insert(cheats::DevCheats::default());
 
     //cmd.insert(MainPlayer);
     //cmd.insert(ThirdPersonCameraTarget);
@@ -266,7 +380,7 @@ fn setup_player(
             ..Default::default()
         },
         TransformBundle {
-            local: Transform::from_xyz(4.0, 2020.0, 11.0), 
+            local: Transform::from_translation(level.spawn_point(1)),
             ..Default::default()
         },
         game_assets.player_model.clone(),
@@ -277,17 +391,53 @@ fn setup_player(
             0.0, 0.50,
         )),
         Collider::capsule_y(0.49, 0.3),
+        colliders::player_groups(),
         RigidBody::Dynamic,
         VisibilityBundle::default(),
         ThirdPersonCameraTarget,
-        TnuaRapier3dIOBundle::default(),
-        TnuaControllerBundle::default(),
-        TnuaAnimatingState::<animations::AnimationState>::default(),
+        // bundles are tuples of at most 15 elements
+        (
+            TnuaRapier3dIOBundle::default(),
+            TnuaControllerBundle::default(),
+            PlayerMotionConfig::default(),
+            AirActions::default(),
+            TnuaAnimatingState::<animations::AnimationState>::default(),
+        ),
+        (
+            PlayerStats::default(),
+            TriggerActivator,
+            interaction::Interactor::default(),
+            cheats::DevCheats::default(),
+        ),
     )).add_rollback();
 
     //cmd.insert(Emitter::default());
 }
 
+/// the players stand on the spawn points of the scene, part of the rollback state
+#[derive(Resource, Clone, Copy, Default)]
+struct ScenePlayersPlaced(bool);
+
+fn reset_scene_players_placed(mut placed: ResMut<ScenePlayersPlaced>) {
+    placed.0 = false;
+}
+
+/// spawn points placed in blender win over the ones from the level file
+fn move_players_to_scene_spawn_points(
+    spawn_points: Res<SceneSpawnPoints>,
+    mut placed: ResMut<ScenePlayersPlaced>,
+    mut player_q: Query<(&Player, &mut Transform)>,
+) {
+    if placed.0 || player_q.is_empty() {
+        return;
+    }
+    for (player, mut transform) in player_q.iter_mut() {
+        let Some(point) = spawn_points.0.get(player.handle % spawn_points.0.len()) else { continue };
+        transform.translation = *point;
+    }
+    placed.0 = true;
+}
+
 #[allow(clippy::type_complexity)]
 fn apply_controls(
     //mut egui_context: EguiContexts,
@@ -295,14 +445,14 @@ fn apply_controls(
     inputs: Res<PlayerInputs<Config>>,
     mut query: Query<(
         &mut Player,
-        //&CharacterMotionConfigForPlatformerExample,
+        &PlayerMotionConfig,
         &mut TnuaController,
         //&mut TnuaCrouchEnforcer,
         &mut TnuaProximitySensor,
         //&TnuaGhostSensor,
         //&mut TnuaSimpleFallThroughPlatformsHelper,
         //&FallingThroughControlScheme,
-        //&mut TnuaSimpleAirActionsCounter,
+        &mut AirActions,
         Option<&MainPlayer>,
     )>,
     cam_q: Query<&Transform, (With<PlayerCamera>, Without<Player>, Without<Head>)>,
@@ -319,14 +469,14 @@ fn apply_controls(
 
     for (
         mut config,
-        //config,
+        motion,
         mut controller,
         //mut crouch_enforcer,
         mut sensor,
         /*ghost_sensor,
         mut fall_through_helper,
-        falling_through_control_scheme,
-        mut air_actions_counter,*/
+        falling_through_control_scheme,*/
+        mut air_actions,
         main_player,
     ) in query.iter_mut()
     {
@@ -340,9 +490,7 @@ fn apply_controls(
             Ok(cam) => cam,
             Err(e) => Err(format!("Error retriving camera: {}", e)).unwrap(),
         };
-        /*air_actions_counter.update(controller.as_mut());
-
-        let crouch = falling_through_control_scheme.perform_and_check_if_still_crouching(
+        /*let crouch = falling_through_control_scheme.perform_and_check_if_still_crouching(
             crouch,
             crouch_just_pressed,
             fall_through_helper.as_mut(),
@@ -350,26 +498,20 @@ fn apply_controls(
             ghost_sensor,
             1.0,
         );*/
-        let mut direction = Vec3::ZERO;
-
         let (state, _) = inputs[config.handle];
 
         let input = state.input;
-        if input & INPUT_UP != 0  {
-            direction += cam.forward();
-        }
-        if input & INPUT_DOWN != 0 {
-            direction += cam.back();
-        }
-        if input & INPUT_LEFT != 0 {
-            direction += cam.left();
-        }
-        if input & INPUT_RIGHT != 0 {
-            direction += cam.right();
-        }
+        let movement = state.movement();
 
-        direction.y = 0.0;
-        direction = direction.clamp_length_max(1.0);
+        let grounded = controller
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .is_some_and(|(_, basis_state)| basis_state.standing_on_entity().is_some());
+        air_actions.update(grounded, input);
+
+        // only the horizontal part of the camera orientation is used for walking
+        let forward = Vec3::new(cam.forward().x, 0.0, cam.forward().z).normalize_or_zero();
+        let right = Vec3::new(cam.right().x, 0.0, cam.right().z).normalize_or_zero();
+        let direction = (forward * movement.y + right * movement.x).clamp_length_max(1.0);
 
         let speed_factor = if input & INPUT_RUN != 0 {
             6.0
@@ -386,27 +528,25 @@ fn apply_controls(
             ..Default::default()
         });
 
-        /*if crouch {
-            controller.action(crouch_enforcer.enforcing(config.crouch.clone()));
+        if input & INPUT_CROUCH != 0 {
+            controller.action(motion.crouch.clone());
         }
 
-        if jump {
+        if input & INPUT_JUMP != 0 {
             controller.action(TnuaBuiltinJump {
-                allow_in_air: air_actions_counter.air_count_for(TnuaBuiltinJump::NAME)
-                    <= config.actions_in_air,
-                ..config.jump.clone()
+                allow_in_air: air_actions.count <= motion.actions_in_air,
+                ..motion.jump.clone()
             });
         }
 
-        if dash {
+        if input & INPUT_DASH != 0 && direction != Vec3::ZERO {
             controller.action(TnuaBuiltinDash {
-                displacement: direction.normalize() * config.dash_distance,
+                displacement: direction.normalize() * motion.dash_distance,
                 desired_forward: direction.normalize(),
-                allow_in_air: air_actions_counter.air_count_for(TnuaBuiltinDash::NAME)
-                    <= config.actions_in_air,
-                ..config.dash.clone()
+                allow_in_air: air_actions.count <= motion.actions_in_air,
+                ..motion.dash.clone()
             });
-        }*/
+        }
 
         // turn player by mouse position
         // if this is not a main player...
@@ -434,6 +574,7 @@ fn foot_steps(
     mut steps_query: Query<(Entity, &GlobalTransform, &mut Foot)>,
     query_root_body: Query<&GlobalTransform, With<Player>>,
     asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
 ) {
     for (foot_entity, t, mut f) in steps_query.iter_mut() {
         let player = query_root_body.get(f.body).unwrap();
@@ -448,6 +589,7 @@ fn foot_steps(
                     source: asset_server.load("02-footstep.ogg"),
                     settings: PlaybackSettings {
                         mode: bevy::audio::PlaybackMode::Remove,
+                        volume: settings.effects(),
                         spatial: true,
                         ..default()
                     },
@@ -0,0 +1,208 @@
+//! The settings of the player, saved with `config` and applied while the game runs.
+//!
+//! Everything which can be changed live is applied as soon as the resource changes, the camera and
+//! the sun also get the settings when they are spawned for a match.
+
+use bevy::{
+    audio::{GlobalVolume, Volume},
+    pbr::DirectionalLightShadowMap,
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::ThirdPersonCamera,
+    config,
+    map::{lights::LightBudget, time_of_day::Sun},
+};
+
+const SETTINGS_CONFIG: &str = "settings";
+
+/// the resolutions which can be picked for a window, the monitor decides in fullscreen
+pub const RESOLUTIONS: &[(u32, u32)] = &[(1280, 720), (1600, 900), (1920, 1080), (2560, 1440), (3840, 2160)];
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Settings::load())
+            .add_systems(Update, (
+                (apply_window_settings, apply_audio_settings).run_if(resource_changed::<Settings>()),
+                apply_shadow_settings,
+                apply_camera_settings,
+            ));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    /// how many point lights get a shadow, see `LightBudget`
+    fn point_shadows(self) -> usize {
+        match self {
+            ShadowQuality::Off => 0,
+            ShadowQuality::Low => 2,
+            ShadowQuality::Medium => 4,
+            ShadowQuality::High => 8,
+        }
+    }
+
+    fn sun_shadow_map(self) -> usize {
+        match self {
+            ShadowQuality::Off | ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 4096,
+        }
+    }
+}
+
+/// Saved as `settings.ron`, fields which are missing in the file keep their defaults.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window_mode: WindowModeSetting,
+    /// the size of the window, not used in fullscreen
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    pub shadows: ShadowQuality,
+    /// vertical field of view in degrees
+    pub fov: f32,
+    pub mouse_sensitivity: f32,
+    /// for the mouse and the gamepad, both read it from the camera
+    pub invert_y: bool,
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    /// the defaults of `NetworkConfig`, the command line still overrides them
+    pub name: String,
+    pub server: String,
+    pub input_delay: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowModeSetting::Windowed,
+            resolution: (1280, 720),
+            vsync: true,
+            shadows: ShadowQuality::Medium,
+            fov: 45.0,
+            mouse_sensitivity: 2.0,
+            invert_y: false,
+            master_volume: 1.0,
+            effects_volume: 1.0,
+            name: "Player".to_string(),
+            server: "ws://10.42.200.56:3536".to_string(),
+            input_delay: 2,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        config::load(SETTINGS_CONFIG)
+    }
+
+    pub fn save(&self) {
+        config::save(SETTINGS_CONFIG, self);
+    }
+
+    /// the volume for sound effects, the master volume is applied by bevy on top of it
+    pub fn effects(&self) -> Volume {
+        Volume::new_relative(self.effects_volume)
+    }
+}
+
+/// a window which was resized by hand keeps its size until the resolution or the mode is picked again
+fn apply_window_settings(
+    settings: Res<Settings>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    #[cfg(not(target_arch = "wasm32"))] mut applied_size: Local<Option<(WindowModeSetting, (u32, u32))>>,
+) {
+    let Ok(mut window) = window_q.get_single_mut() else { return };
+
+    let mode = match settings.window_mode {
+        WindowModeSetting::Windowed => WindowMode::Windowed,
+        WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+        WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+    };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+
+    // the browser decides the size of the canvas
+    #[cfg(not(target_arch = "wasm32"))]
+    if *applied_size != Some((settings.window_mode, settings.resolution)) {
+        *applied_size = Some((settings.window_mode, settings.resolution));
+        if mode == WindowMode::Windowed {
+            let (width, height) = settings.resolution;
+            window.resolution.set(width as f32, height as f32);
+        }
+    }
+
+    let present_mode = if settings.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync };
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+}
+
+/// only sounds which start after the change are affected, the running ones are short
+fn apply_audio_settings(settings: Res<Settings>, mut global_volume: ResMut<GlobalVolume>) {
+    *global_volume = GlobalVolume::new(settings.master_volume);
+}
+
+/// the sun is spawned with every match, so this runs every frame and only writes differences
+fn apply_shadow_settings(
+    settings: Res<Settings>,
+    mut budget: ResMut<LightBudget>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut sun_q: Query<&mut DirectionalLight, With<Sun>>,
+) {
+    let quality = settings.shadows;
+    if budget.max_shadows != quality.point_shadows() {
+        budget.max_shadows = quality.point_shadows();
+    }
+    if shadow_map.size != quality.sun_shadow_map() {
+        shadow_map.size = quality.sun_shadow_map();
+    }
+    for mut light in sun_q.iter_mut() {
+        let enabled = quality != ShadowQuality::Off;
+        if light.shadows_enabled != enabled {
+            light.shadows_enabled = enabled;
+        }
+    }
+}
+
+fn apply_camera_settings(
+    settings: Res<Settings>,
+    mut camera_q: Query<(&mut ThirdPersonCamera, &mut Projection)>,
+) {
+    for (mut camera, mut projection) in camera_q.iter_mut() {
+        if camera.mouse_sensitivity != settings.mouse_sensitivity || camera.invert_y != settings.invert_y {
+            camera.mouse_sensitivity = settings.mouse_sensitivity;
+            camera.invert_y = settings.invert_y;
+        }
+        if let Projection::Perspective(perspective) = projection.as_ref() {
+            let fov = settings.fov.to_radians();
+            if (perspective.fov - fov).abs() > f32::EPSILON {
+                if let Projection::Perspective(perspective) = projection.as_mut() {
+                    perspective.fov = fov;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! The developer console, opened with the backtick key.
+//!
+//! Commands get the whole world. The ones which change the match are only allowed in an offline
+//! match, online they would change the simulation of one peer only. Those go through
+//! `player::cheats`, which applies them in a frame of the `GgrsSchedule`.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::{
+    despawn_screen,
+    game::{Config, MatchClock, RestartMatch},
+    lobby::PlayerNames,
+    map::{props, registry::{find_map, MapRotation, MAPS}},
+    player::{
+        cheats::{Cheat, DevCheats, ScheduledCheats, Vital},
+        MainPlayer, Player,
+    },
+    AppState,
+};
+
+use super::MyUiAssets;
+
+/// older lines are dropped
+const MAX_LOG_LINES: usize = 200;
+/// lines which fit into the panel
+const VISIBLE_LINES: usize = 14;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<ConsoleState>()
+            .init_resource::<ConsoleLog>()
+            .init_resource::<ConsoleInput>()
+            .add_systems(Update, toggle_console)
+            .add_systems(OnEnter(ConsoleState::Open), console_setup)
+            .add_systems(OnExit(ConsoleState::Open), despawn_screen::<OnConsole>)
+            .add_systems(Update, (
+                type_command,
+                run_console_command,
+                update_console_text,
+            ).chain().run_if(in_state(ConsoleState::Open)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum ConsoleState {
+    #[default]
+    Closed,
+    Open,
+}
+
+#[derive(Resource, Default)]
+struct ConsoleLog(VecDeque<String>);
+
+impl ConsoleLog {
+    fn push(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.0.len() == MAX_LOG_LINES {
+                self.0.pop_front();
+            }
+            self.0.push_back(line.to_string());
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ConsoleInput {
+    text: String,
+    history: Vec<String>,
+    /// the history entry shown while browsing with up and down
+    browsing: Option<usize>,
+    /// run in the exclusive system, which has the world
+    submitted: Option<String>,
+}
+
+#[derive(Component)]
+struct OnConsole;
+
+#[derive(Component)]
+struct ConsoleLogText;
+
+#[derive(Component)]
+struct ConsoleInputText;
+
+type CommandResult = Result<String, String>;
+
+struct ConsoleCommand {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    /// changes the match, refused online
+    gameplay: bool,
+    run: fn(&mut World, &[&str]) -> CommandResult,
+}
+
+const COMMANDS: &[ConsoleCommand] = &[
+    ConsoleCommand { name: "help", usage: "help", help: "lists the commands", gameplay: false, run: help },
+    ConsoleCommand { name: "clear", usage: "clear", help: "empties the console", gameplay: false, run: clear },
+    ConsoleCommand { name: "teleport", usage: "teleport <x> <y> <z>", help: "moves your player", gameplay: true, run: teleport },
+    ConsoleCommand { name: "noclip", usage: "noclip", help: "fly through walls", gameplay: true, run: noclip },
+    ConsoleCommand { name: "god", usage: "god", help: "keeps your vitals full", gameplay: true, run: god },
+    ConsoleCommand { name: "give", usage: "give <health|stamina|mana> [amount]", help: "refills a vital", gameplay: true, run: give },
+    ConsoleCommand { name: "spawn", usage: "spawn <prop>", help: "puts a prop in front of you", gameplay: true, run: spawn },
+    ConsoleCommand { name: "set_timescale", usage: "set_timescale <factor>", help: "slows down or speeds up the game", gameplay: true, run: set_timescale },
+    ConsoleCommand { name: "map", usage: "map <name>", help: "restarts the match on another map", gameplay: true, run: change_map },
+    ConsoleCommand { name: "net_stats", usage: "net_stats", help: "ping and frame advantage of the peers", gameplay: false, run: net_stats },
+    ConsoleCommand { name: "inspector", usage: "inspector", help: "shows or hides the world inspector", gameplay: false, run: inspector },
+    ConsoleCommand { name: "debug", usage: "debug [layer|all]", help: "toggles a layer of debug gizmos", gameplay: false, run: debug_layer },
+];
+
+/// Escape is also the menu key, it is used up here so closing the console doesn't open the menu
+pub fn toggle_console(
+    mut keys: ResMut<Input<KeyCode>>,
+    state: Res<State<ConsoleState>>,
+    mut next_state: ResMut<NextState<ConsoleState>>,
+) {
+    let open = state.get() == &ConsoleState::Open;
+    if open && keys.just_pressed(KeyCode::Escape) {
+        keys.clear_just_pressed(KeyCode::Escape);
+        next_state.set(ConsoleState::Closed);
+    } else if keys.just_pressed(KeyCode::Grave) {
+        next_state.set(if open { ConsoleState::Closed } else { ConsoleState::Open });
+    }
+}
+
+fn console_setup(mut commands: Commands, ui_assets: Res<MyUiAssets>, log: Res<ConsoleLog>, input: Res<ConsoleInput>) {
+    let text_style = TextStyle {
+        font: ui_assets.ui_font.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(40.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::End,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                // over every other screen
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            OnConsole,
+        ))
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_section(log_text(&log), text_style.clone()), ConsoleLogText));
+            parent.spawn((
+                TextBundle::from_section(
+                    input_text(&input),
+                    TextStyle {
+                        color: Color::rgb(1.0, 0.85, 0.4),
+                        ..text_style
+                    },
+                ),
+                ConsoleInputText,
+            ));
+        });
+}
+
+fn log_text(log: &ConsoleLog) -> String {
+    let skip = log.0.len().saturating_sub(VISIBLE_LINES);
+    log.0.iter().skip(skip).cloned().collect::<Vec<_>>().join("\n")
+}
+
+fn input_text(input: &ConsoleInput) -> String {
+    format!("> {}_", input.text)
+}
+
+fn type_command(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut input: ResMut<ConsoleInput>,
+) {
+    for character in characters.read() {
+        // the key which opened the console
+        if character.char == '`' || character.char.is_control() {
+            continue;
+        }
+        input.text.push(character.char);
+        input.browsing = None;
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        input.text.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Up) && !input.history.is_empty() {
+        let index = input.browsing.map_or(input.history.len() - 1, |index| index.saturating_sub(1));
+        input.text = input.history[index].clone();
+        input.browsing = Some(index);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        if let Some(index) = input.browsing {
+            let next = index + 1;
+            input.browsing = (next < input.history.len()).then_some(next);
+            input.text = input.browsing.map(|index| input.history[index].clone()).unwrap_or_default();
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut input.text).trim().to_string();
+        input.browsing = None;
+        if line.is_empty() {
+            return;
+        }
+        if input.history.last() != Some(&line) {
+            input.history.push(line.clone());
+        }
+        input.submitted = Some(line);
+    }
+}
+
+fn run_console_command(world: &mut World) {
+    let Some(line) = world.resource_mut::<ConsoleInput>().submitted.take() else { return };
+
+    world.resource_mut::<ConsoleLog>().push(&format!("> {line}"));
+    let output = execute(world, &line);
+    let mut log = world.resource_mut::<ConsoleLog>();
+    match output {
+        Ok(text) => log.push(&text),
+        Err(text) => log.push(&format!("error: {text}")),
+    }
+}
+
+fn execute(world: &mut World, line: &str) -> CommandResult {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else { return Ok(String::new()) };
+    let args: Vec<&str> = words.collect();
+
+    let Some(command) = COMMANDS.iter().find(|command| command.name == name) else {
+        return Err(format!("unknown command {name}, try help"));
+    };
+    if command.gameplay && !cheats_allowed(world) {
+        return Err(format!("{name} only works in an offline match, online it would only change your game"));
+    }
+    (command.run)(world, &args)
+}
+
+/// an offline match is a synctest session, every player is local
+fn cheats_allowed(world: &World) -> bool {
+    world.resource::<State<AppState>>().get() == &AppState::InGame
+        && matches!(world.get_resource::<Session<Config>>(), Some(Session::SyncTest(_)))
+}
+
+/// the player entity, its handle and its camera
+fn main_player(world: &mut World) -> Result<(Entity, usize, Option<Entity>), String> {
+    world
+        .query_filtered::<(Entity, &Player), With<MainPlayer>>()
+        .iter(world)
+        .next()
+        .map(|(e, player)| (e, player.handle, player.camera))
+        .ok_or_else(|| "there is no local player".to_string())
+}
+
+fn schedule_cheat(world: &mut World, cheat: Cheat) -> Result<(), String> {
+    let (_, handle, _) = main_player(world)?;
+    let clock = *world.resource::<MatchClock>();
+    world.resource_mut::<ScheduledCheats>().schedule(&clock, handle, cheat);
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<&&str>, usage: &str) -> Result<T, String> {
+    arg.and_then(|arg| arg.parse().ok()).ok_or_else(|| format!("usage: {usage}"))
+}
+
+fn help(_world: &mut World, _args: &[&str]) -> CommandResult {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            let offline = if command.gameplay { " (offline)" } else { "" };
+            format!("{} - {}{offline}", command.usage, command.help)
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn clear(world: &mut World, _args: &[&str]) -> CommandResult {
+    world.resource_mut::<ConsoleLog>().0.clear();
+    Ok(String::new())
+}
+
+fn teleport(world: &mut World, args: &[&str]) -> CommandResult {
+    let usage = "teleport <x> <y> <z>";
+    let position = Vec3::new(parse(args.first(), usage)?, parse(args.get(1), usage)?, parse(args.get(2), usage)?);
+    schedule_cheat(world, Cheat::Teleport(position))?;
+    Ok(format!("teleporting to {position}"))
+}
+
+/// the toggle is applied in the next frame, so the message tells the state it will have
+fn toggle_cheat(world: &mut World, cheat: Cheat, name: &str, enabled: fn(&DevCheats) -> bool) -> CommandResult {
+    let (e, _, _) = main_player(world)?;
+    let was_enabled = world.get::<DevCheats>(e).is_some_and(enabled);
+    schedule_cheat(world, cheat)?;
+    Ok(format!("{name} {}", if was_enabled { "off" } else { "on" }))
+}
+
+fn noclip(world: &mut World, _args: &[&str]) -> CommandResult {
+    toggle_cheat(world, Cheat::ToggleNoclip, "noclip", |cheats| cheats.noclip)
+}
+
+fn god(world: &mut World, _args: &[&str]) -> CommandResult {
+    toggle_cheat(world, Cheat::ToggleGod, "god", |cheats| cheats.god)
+}
+
+fn give(world: &mut World, args: &[&str]) -> CommandResult {
+    let vital = match args.first().copied() {
+        Some("health") => Vital::Health,
+        Some("stamina") => Vital::Stamina,
+        Some("mana") => Vital::Mana,
+        Some(item) => return Err(format!("there are no items like {item} yet, only health, stamina and mana")),
+        None => return Err("usage: give <health|stamina|mana> [amount]".to_string()),
+    };
+    let amount = match args.get(1) {
+        Some(_) => parse(args.get(1), "give <health|stamina|mana> [amount]")?,
+        None => 100.0,
+    };
+    schedule_cheat(world, Cheat::Give(vital, amount))?;
+    Ok(format!("giving {amount} {}", args[0]))
+}
+
+fn spawn(world: &mut World, args: &[&str]) -> CommandResult {
+    let Some(name) = args.first() else { return Err("usage: spawn <prop>".to_string()) };
+    let model = if name.ends_with(".glb") { name.to_string() } else { format!("{name}.glb") };
+
+    // the asset server only logs a missing file, the browser can't be asked beforehand
+    #[cfg(not(target_arch = "wasm32"))]
+    if !std::path::Path::new("assets").join(&model).exists() {
+        return Err(format!("there is no model {model}"));
+    }
+
+    let (e, _, camera) = main_player(world)?;
+    let position = world.get::<Transform>(e).map(|transform| transform.translation).unwrap_or_default();
+    let forward = camera
+        .and_then(|camera| world.get::<Transform>(camera))
+        .map(|camera| Vec3::new(camera.forward().x, 0.0, camera.forward().z).normalize_or_zero())
+        .unwrap_or(Vec3::NEG_Z);
+    // the character floats one unit above the ground
+    let transform = Transform::from_translation(position + forward * 2.0 - Vec3::Y)
+        .looking_to(-forward, Vec3::Y);
+
+    let collider = props::prop_kind(&model).collider;
+    schedule_cheat(world, Cheat::Spawn { model: model.clone(), transform })?;
+
+    // hulls are built from the meshes while the level loads, not in the middle of the simulation
+    if matches!(collider, props::PropCollider::ConvexHull) {
+        Ok(format!("spawned {model}, it has no collider"))
+    } else {
+        Ok(format!("spawned {model}"))
+    }
+}
+
+fn set_timescale(world: &mut World, args: &[&str]) -> CommandResult {
+    let scale: f32 = parse(args.first(), "set_timescale <factor>")?;
+    let scale = scale.clamp(0.1, 4.0);
+    world.resource_mut::<Time<Virtual>>().set_relative_speed(scale);
+    Ok(format!("timescale {scale}"))
+}
+
+fn change_map(world: &mut World, args: &[&str]) -> CommandResult {
+    let Some(map) = args.first().and_then(|name| find_map(name)) else {
+        let names: Vec<&str> = MAPS.iter().map(|map| map.name).collect();
+        return Err(format!("usage: map <name>, the maps are {}", names.join(", ")));
+    };
+
+    world.resource_mut::<MapRotation>().play_next(map.name);
+    world.insert_resource(RestartMatch);
+    world.resource_mut::<NextState<AppState>>().set(AppState::MainMenu);
+    Ok(format!("loading {}", map.title))
+}
+
+fn net_stats(world: &mut World, _args: &[&str]) -> CommandResult {
+    let names = world.get_resource::<PlayerNames>();
+    let name = |handle: usize| names.map_or_else(|| format!("Player {}", handle + 1), |names| names.get(handle));
+
+    match world.get_resource::<Session<Config>>() {
+        None => Err("not in a match".to_string()),
+        Some(Session::SyncTest(_)) => Ok("offline, every player is local".to_string()),
+        Some(Session::P2P(session)) => {
+            let mut lines = vec![format!("{:?}, {} frames ahead", session.current_state(), session.frames_ahead())];
+            for handle in session.remote_player_handles() {
+                match session.network_stats(handle) {
+                    Ok(stats) => lines.push(format!(
+                        "{}: ping {} ms, {} kbps, {} frames behind, send queue {}",
+                        name(handle),
+                        stats.ping,
+                        stats.kbps_sent,
+                        stats.remote_frames_behind,
+                        stats.send_queue_len
+                    )),
+                    Err(err) => lines.push(format!("{}: {err}", name(handle))),
+                }
+            }
+            Ok(lines.join("\n"))
+        }
+        Some(Session::Spectator(session)) => match session.network_stats() {
+            Ok(stats) => Ok(format!(
+                "spectating, ping {} ms to the host, {} frames behind",
+                stats.ping, stats.local_frames_behind
+            )),
+            Err(err) => Err(err.to_string()),
+        },
+    }
+}
+
+#[cfg(feature = "dev")]
+fn inspector(world: &mut World, _args: &[&str]) -> CommandResult {
+    let mut tools = world.resource_mut::<crate::dev::DevTools>();
+    tools.inspector = !tools.inspector;
+    Ok(format!("inspector {}", if tools.inspector { "shown" } else { "hidden" }))
+}
+
+#[cfg(not(feature = "dev"))]
+fn inspector(_world: &mut World, _args: &[&str]) -> CommandResult {
+    Err("the inspector is only part of builds with --features dev".to_string())
+}
+
+#[cfg(feature = "dev")]
+fn debug_layer(world: &mut World, args: &[&str]) -> CommandResult {
+    use crate::dev::layers::{DebugLayer, DebugLayers};
+
+    let mut layers = world.resource_mut::<DebugLayers>();
+    let layer = match args.first().copied() {
+        Some("all") => {
+            let shown = layers.toggle_all();
+            return Ok(format!("all layers {}", if shown { "shown" } else { "hidden" }));
+        }
+        Some(name) => DebugLayer::from_name(name),
+        None => None,
+    };
+    let Some(layer) = layer else {
+        let names: Vec<String> = DebugLayer::ALL
+            .iter()
+            .map(|layer| if layers.0.contains(layer) { format!("{}*", layer.name()) } else { layer.name().to_string() })
+            .collect();
+        return Err(format!("usage: debug [layer|all], the layers are {} (* is shown)", names.join(", ")));
+    };
+    let shown = layers.toggle(layer);
+    Ok(format!("{} {}", layer.name(), if shown { "shown" } else { "hidden" }))
+}
+
+#[cfg(not(feature = "dev"))]
+fn debug_layer(_world: &mut World, _args: &[&str]) -> CommandResult {
+    Err("the debug layers are only part of builds with --features dev".to_string())
+}
+
+fn update_console_text(
+    log: Res<ConsoleLog>,
+    input: Res<ConsoleInput>,
+    mut log_q: Query<&mut Text, (With<ConsoleLogText>, Without<ConsoleInputText>)>,
+    mut input_q: Query<&mut Text, (With<ConsoleInputText>, Without<ConsoleLogText>)>,
+) {
+    if log.is_changed() {
+        for mut text in log_q.iter_mut() {
+            text.sections[0].value = log_text(&log);
+        }
+    }
+    if input.is_changed() {
+        for mut text in input_q.iter_mut() {
+            text.sections[0].value = input_text(&input);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::{AppState, despawn_screen, input::gamepad::ActiveGamepad};
+
+use super::MyUiAssets;
+
+pub struct GamepadStatusPlugin;
+
+impl Plugin for GamepadStatusPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::InGame), gamepad_status_setup)
+            .add_systems(Update, update_gamepad_status.run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), despawn_screen::<GamepadStatusText>);
+    }
+}
+
+#[derive(Component)]
+pub struct GamepadStatusText;
+
+fn gamepad_status_text(active: &ActiveGamepad) -> String {
+    match (&active.gamepad, &active.name) {
+        (Some(_), Some(name)) => format!("Gamepad: {name}"),
+        (Some(gamepad), None) => format!("Gamepad: #{}", gamepad.id),
+        _ => String::new(),
+    }
+}
+
+fn gamepad_status_setup(
+    mut commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    active: Res<ActiveGamepad>,
+) {
+    commands.spawn((
+        TextBundle::from_section(
+            gamepad_status_text(&active),
+            TextStyle {
+                font: ui_assets.ui_font.clone(),
+                font_size: 18.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        GamepadStatusText,
+    ));
+}
+
+fn update_gamepad_status(
+    active: Res<ActiveGamepad>,
+    mut text_q: Query<&mut Text, With<GamepadStatusText>>,
+) {
+    if !active.is_changed() {
+        return;
+    }
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = gamepad_status_text(&active);
+    }
+}
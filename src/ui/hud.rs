@@ -0,0 +1,429 @@
+//! The HUD of the local player: the vitals, a crosshair and what the player looks at can be used for.
+//!
+//! Sizes are relative to the window (`Vh`), text sizes are scaled with the window height.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    despawn_screen,
+    input::action_map::{Action, ActionMap},
+    map::doors::Door,
+    player::{interaction::LookTarget, MainPlayer, PlayerStats},
+    AppState,
+};
+
+use super::MyUiAssets;
+
+/// text sizes are made for a window of this height
+const REFERENCE_HEIGHT: f32 = 720.0;
+/// the lost part of a bar stays visible for this long before it shrinks
+const TRAIL_HOLD: f32 = 0.6;
+/// how fast the lost part shrinks, in bar lengths per second
+const TRAIL_SPEED: f32 = 0.5;
+/// below this part of the maximum the health warning is shown
+const LOW_HEALTH: f32 = 0.25;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HudVitals>()
+            .add_systems(OnEnter(AppState::InGame), hud_setup)
+            .add_systems(Update, (
+                update_vitals,
+                update_crosshair,
+                update_prompt,
+                scale_hud_text,
+            ).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), despawn_screen::<OnHud>);
+    }
+}
+
+#[derive(Component)]
+pub struct OnHud;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vital {
+    Health,
+    Stamina,
+    Mana,
+}
+
+impl Vital {
+    const ALL: [Vital; 3] = [Vital::Health, Vital::Stamina, Vital::Mana];
+
+    fn values(self, stats: &PlayerStats) -> (f32, f32) {
+        match self {
+            Vital::Health => (stats.health, stats.max_health),
+            Vital::Stamina => (stats.stamina, stats.max_stamina),
+            Vital::Mana => (stats.mana, stats.max_mana),
+        }
+    }
+
+    fn fraction(self, stats: &PlayerStats) -> f32 {
+        let (value, max) = self.values(stats);
+        if max > 0.0 { (value / max).clamp(0.0, 1.0) } else { 0.0 }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Vital::Health => Color::rgb(0.75, 0.12, 0.1),
+            Vital::Stamina => Color::rgb(0.85, 0.7, 0.15),
+            Vital::Mana => Color::rgb(0.2, 0.4, 0.9),
+        }
+    }
+}
+
+/// the bars and the vital text are only shown if there is a local player, not for spectators
+#[derive(Component)]
+struct VitalPanel;
+
+#[derive(Component)]
+struct VitalFill(Vital);
+
+/// the part of the bar which was just lost
+#[derive(Component)]
+struct VitalTrail(Vital);
+
+#[derive(Component)]
+struct VitalText(Vital);
+
+/// red over the whole screen when the player is hit
+#[derive(Component)]
+struct DamageFlash;
+
+/// a pulsing red frame while the health is low
+#[derive(Component)]
+struct LowHealthFrame;
+
+#[derive(Component)]
+struct Crosshair;
+
+#[derive(Component)]
+struct InteractionPrompt;
+
+/// the font size at the reference height of the window
+#[derive(Component)]
+struct HudText(f32);
+
+/// what the bars show, they follow the stats smoothly
+#[derive(Resource, Default)]
+struct HudVitals {
+    shown: [f32; 3],
+    trail: [f32; 3],
+    trail_hold: [f32; 3],
+    last_health: Option<f32>,
+    flash: f32,
+}
+
+fn hud_setup(mut commands: Commands, ui_assets: Res<MyUiAssets>, mut vitals: ResMut<HudVitals>) {
+    *vitals = HudVitals::default();
+
+    let text = |value: &str, font_size: f32| {
+        (
+            TextBundle::from_section(
+                value,
+                TextStyle {
+                    font: ui_assets.ui_font.clone(),
+                    font_size,
+                    color: Color::WHITE,
+                },
+            ),
+            HudText(font_size),
+        )
+    };
+    let full_screen = Style {
+        position_type: PositionType::Absolute,
+        width: Val::Percent(100.0),
+        height: Val::Percent(100.0),
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: full_screen.clone(),
+                ..default()
+            },
+            OnHud,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: full_screen.clone(),
+                    background_color: Color::NONE.into(),
+                    ..default()
+                },
+                DamageFlash,
+            ));
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        border: UiRect::all(Val::Vh(2.0)),
+                        ..full_screen.clone()
+                    },
+                    border_color: Color::NONE.into(),
+                    ..default()
+                },
+                LowHealthFrame,
+            ));
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Percent(50.0),
+                        top: Val::Percent(50.0),
+                        width: Val::Vh(0.6),
+                        height: Val::Vh(0.6),
+                        margin: UiRect {
+                            left: Val::Vh(-0.3),
+                            top: Val::Vh(-0.3),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.8).into(),
+                    ..default()
+                },
+                Crosshair,
+            ));
+
+            // centered below the crosshair
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Percent(55.0),
+                        width: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((text("", 24.0), InteractionPrompt));
+                });
+
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Vh(3.0),
+                            bottom: Val::Vh(3.0),
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Vh(0.8),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    VitalPanel,
+                ))
+                .with_children(|parent| {
+                    for vital in Vital::ALL {
+                        let height = if vital == Vital::Health { 2.2 } else { 1.4 };
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Vh(1.0),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn(NodeBundle {
+                                        style: Style {
+                                            width: Val::Vh(32.0),
+                                            height: Val::Vh(height),
+                                            padding: UiRect::all(Val::Vh(0.25)),
+                                            ..default()
+                                        },
+                                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                                        ..default()
+                                    })
+                                    .with_children(|parent| {
+                                        let bar = |width| Style {
+                                            position_type: PositionType::Absolute,
+                                            left: Val::Vh(0.25),
+                                            top: Val::Vh(0.25),
+                                            bottom: Val::Vh(0.25),
+                                            width: Val::Percent(width),
+                                            ..default()
+                                        };
+                                        parent.spawn((
+                                            NodeBundle {
+                                                style: bar(0.0),
+                                                background_color: Color::rgba(1.0, 0.9, 0.8, 0.7).into(),
+                                                ..default()
+                                            },
+                                            VitalTrail(vital),
+                                        ));
+                                        parent.spawn((
+                                            NodeBundle {
+                                                style: bar(0.0),
+                                                background_color: vital.color().into(),
+                                                ..default()
+                                            },
+                                            VitalFill(vital),
+                                        ));
+                                    });
+                                parent.spawn((text("", if vital == Vital::Health { 18.0 } else { 14.0 }), VitalText(vital)));
+                            });
+                    }
+                });
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_vitals(
+    time: Res<Time>,
+    mut vitals: ResMut<HudVitals>,
+    stats_q: Query<&PlayerStats, With<MainPlayer>>,
+    mut panel_q: Query<&mut Visibility, With<VitalPanel>>,
+    mut fill_q: Query<(&VitalFill, &mut Style, &mut BackgroundColor), Without<VitalTrail>>,
+    mut trail_q: Query<(&VitalTrail, &mut Style), Without<VitalFill>>,
+    mut text_q: Query<(&VitalText, &mut Text)>,
+    mut flash_q: Query<&mut BackgroundColor, (With<DamageFlash>, Without<VitalFill>)>,
+    mut frame_q: Query<&mut BorderColor, With<LowHealthFrame>>,
+) {
+    let stats = stats_q.get_single().ok();
+    for mut visibility in panel_q.iter_mut() {
+        let wanted = if stats.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+    let Some(stats) = stats else { return };
+
+    let dt = time.delta_seconds();
+    let vitals = &mut *vitals;
+    for (i, vital) in Vital::ALL.into_iter().enumerate() {
+        let target = vital.fraction(stats);
+        vitals.shown[i] += (target - vitals.shown[i]) * (dt * 12.0).min(1.0);
+
+        // the trail waits a moment at the old value, then shrinks to the new one
+        if target >= vitals.trail[i] {
+            vitals.trail[i] = target;
+            vitals.trail_hold[i] = TRAIL_HOLD;
+        } else if vitals.trail_hold[i] > 0.0 {
+            vitals.trail_hold[i] -= dt;
+        } else {
+            vitals.trail[i] = (vitals.trail[i] - TRAIL_SPEED * dt).max(target);
+        }
+    }
+
+    // a rollback can move the health back and forth by a frame, small changes are no hits
+    if vitals.last_health.is_some_and(|last| stats.health < last - 0.5) {
+        vitals.flash = 1.0;
+    }
+    vitals.last_health = Some(stats.health);
+    vitals.flash = (vitals.flash - dt * 2.5).max(0.0);
+
+    let health = Vital::Health.fraction(stats);
+    let pulse = if health < LOW_HEALTH && stats.health > 0.0 {
+        0.5 + 0.5 * (time.elapsed_seconds() * 6.0).sin()
+    } else {
+        0.0
+    };
+
+    for (VitalFill(vital), mut style, mut color) in fill_q.iter_mut() {
+        style.width = Val::Percent(vitals.shown[*vital as usize] * 100.0);
+        let wanted = if *vital == Vital::Health {
+            // the bar gets brighter with the pulse of the warning
+            let base = vital.color();
+            Color::rgb(base.r() + 0.25 * pulse, base.g() + 0.15 * pulse, base.b() + 0.1 * pulse)
+        } else {
+            vital.color()
+        };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+    for (VitalTrail(vital), mut style) in trail_q.iter_mut() {
+        style.width = Val::Percent(vitals.trail[*vital as usize] * 100.0);
+    }
+    for (VitalText(vital), mut text) in text_q.iter_mut() {
+        let (value, _) = vital.values(stats);
+        let value = format!("{:.0}", value.max(0.0).ceil());
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+
+    for mut color in flash_q.iter_mut() {
+        color.0 = Color::rgba(0.8, 0.0, 0.0, 0.35 * vitals.flash);
+    }
+    for mut color in frame_q.iter_mut() {
+        color.0 = Color::rgba(0.8, 0.0, 0.0, 0.45 * pulse);
+    }
+}
+
+/// the crosshair gets bigger on something which can be used, spectators don't have one
+fn update_crosshair(
+    target: Res<LookTarget>,
+    player_q: Query<(), With<MainPlayer>>,
+    mut crosshair_q: Query<(&mut Style, &mut BackgroundColor, &mut Visibility), With<Crosshair>>,
+) {
+    let size = if target.entity.is_some() { 1.0 } else { 0.6 };
+    let color = if target.entity.is_some() { Color::rgb(1.0, 0.85, 0.4) } else { Color::rgba(1.0, 1.0, 1.0, 0.8) };
+    let visibility = if player_q.is_empty() { Visibility::Hidden } else { Visibility::Inherited };
+
+    for (mut style, mut background, mut crosshair_visibility) in crosshair_q.iter_mut() {
+        if style.width != Val::Vh(size) {
+            style.width = Val::Vh(size);
+            style.height = Val::Vh(size);
+            style.margin = UiRect {
+                left: Val::Vh(-size / 2.0),
+                top: Val::Vh(-size / 2.0),
+                ..default()
+            };
+        }
+        if background.0 != color {
+            background.0 = color;
+        }
+        if *crosshair_visibility != visibility {
+            *crosshair_visibility = visibility;
+        }
+    }
+}
+
+/// shows what the player looks at can be used for, e.g. `[E] Open`
+fn update_prompt(
+    target: Res<LookTarget>,
+    action_map: Res<ActionMap>,
+    door_q: Query<&Door>,
+    mut text_q: Query<&mut Text, With<InteractionPrompt>>,
+) {
+    let verb = match target.entity.map(|e| door_q.get(e)) {
+        Some(Ok(door)) if door.open => "Close",
+        Some(Ok(_)) => "Open",
+        Some(Err(_)) => "Use",
+        None => "",
+    };
+    let prompt = match action_map.get(Action::Interact).first() {
+        Some(binding) if !verb.is_empty() => format!("[{}] {verb}", binding.label()),
+        _ => verb.to_string(),
+    };
+
+    for mut text in text_q.iter_mut() {
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.clone();
+        }
+    }
+}
+
+fn scale_hud_text(window_q: Query<&Window, With<PrimaryWindow>>, mut text_q: Query<(&HudText, &mut Text)>) {
+    let Ok(window) = window_q.get_single() else { return };
+    let scale = (window.height() / REFERENCE_HEIGHT).max(0.5);
+
+    for (HudText(size), mut text) in text_q.iter_mut() {
+        let font_size = size * scale;
+        if text.sections[0].style.font_size != font_size {
+            text.sections[0].style.font_size = font_size;
+        }
+    }
+}
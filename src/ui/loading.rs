@@ -0,0 +1,348 @@
+//! One loading screen for the start of the game and for every match.
+//!
+//! The bar shows what iyes_progress collects from the tracked systems and assets, the line below
+//! it says what is waited for. If loading can't go on, the error is shown instead and escape goes
+//! back to the menu. An asset which fails to load is such an error, so it has to be added with
+//! `TrackedLoading` to be watched.
+
+use bevy::{asset::LoadState, ecs::system::SystemParam, prelude::*, utils::HashSet};
+use bevy_ggrs::Session;
+use bevy_matchbox::prelude::*;
+use iyes_progress::{prelude::AssetsLoading, ProgressCounter};
+
+use crate::{
+    despawn_screen,
+    game::{load_game_assets, Config, NetworkConfig},
+    input::gamepad::ActiveGamepad,
+    lobby::{Lobby, PeerRole},
+    map::{registry::find_map, ActiveLevel, AgreedMap, LevelMap, MapGenerationColliderStatus},
+    AppState,
+};
+
+use super::{load_ui_assets, MyUiAssets};
+
+/// without an id from the signaling server after this many seconds it can't be reached
+const SERVER_TIMEOUT: f32 = 10.0;
+/// loading gives up if the progress doesn't move for this long, waiting for players doesn't count
+const STALL_TIMEOUT: f32 = 60.0;
+
+pub struct LoadingScreenPlugin;
+
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WatchedAssets>()
+            // the screen needs the font, which is inserted with commands
+            .add_systems(OnEnter(AppState::Splash), (load_ui_assets, apply_deferred, loading_screen_setup, load_game_assets).chain())
+            .add_systems(OnEnter(AppState::GameLoading), loading_screen_setup)
+            .add_systems(Update, (
+                report_failed_assets,
+                update_loading_status,
+                update_progress_bar,
+                leave_after_error.run_if(in_state(AppState::GameLoading)),
+            ).chain().run_if(in_state(AppState::Splash).or_else(in_state(AppState::GameLoading))))
+            .add_systems(OnExit(AppState::Splash), (despawn_screen::<OnLoadingScreen>, forget_watched_assets))
+            .add_systems(OnExit(AppState::GameLoading), (despawn_screen::<OnLoadingScreen>, clear_loading_error, forget_watched_assets));
+    }
+}
+
+#[derive(Component)]
+pub struct OnLoadingScreen;
+
+#[derive(Component)]
+struct ProgressBar;
+
+#[derive(Component)]
+struct LoadingStatusText;
+
+#[derive(Component)]
+struct LoadingHintText;
+
+/// Loading can't go on, the message is shown instead of the status.
+#[derive(Resource, Debug, Clone)]
+pub struct LoadingError(pub String);
+
+/// the assets which loading waits for, a glTF file is added next to its scenes because only the
+/// file is marked as failed
+#[derive(Resource, Default)]
+pub struct WatchedAssets(HashSet<UntypedHandle>);
+
+/// Adds handles to the loading progress of iyes_progress and to `WatchedAssets`.
+#[derive(SystemParam)]
+pub struct TrackedLoading<'w> {
+    progress: ResMut<'w, AssetsLoading>,
+    watched: ResMut<'w, WatchedAssets>,
+}
+
+impl TrackedLoading<'_> {
+    /// the same handle twice is only counted once
+    pub fn add<A: Asset>(&mut self, handle: &Handle<A>) {
+        if self.watched.0.insert(handle.clone().untyped()) {
+            self.progress.add(handle);
+        }
+    }
+}
+
+/// when loading started and when the progress moved the last time, in seconds since startup
+#[derive(Resource, Default)]
+struct LoadingTimes {
+    started: f32,
+    done: u32,
+    changed: f32,
+}
+
+fn loading_screen_setup(mut commands: Commands, ui_assets: Res<MyUiAssets>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    commands.insert_resource(LoadingTimes {
+        started: now,
+        done: 0,
+        changed: now,
+    });
+    commands.remove_resource::<LoadingError>();
+
+    let text_style = |font_size| TextStyle {
+        font: ui_assets.ui_font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                ..default()
+            },
+            OnLoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                style: Style {
+                    width: Val::Px(300.0),
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+                image: UiImage::new(ui_assets.logo.clone()),
+                ..default()
+            });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(360.0),
+                        height: Val::Px(14.0),
+                        padding: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::rgb(0.85, 0.85, 0.85).into(),
+                            ..default()
+                        },
+                        ProgressBar,
+                    ));
+                });
+            parent.spawn((TextBundle::from_section("", text_style(24.0)), LoadingStatusText));
+            parent.spawn((TextBundle::from_section("", text_style(18.0)), LoadingHintText));
+        });
+}
+
+/// what the map part of loading is doing, in the order in which it happens
+#[derive(SystemParam)]
+struct MapLoading<'w, 's> {
+    agreed_map: Option<Res<'w, AgreedMap>>,
+    active_level: Option<Res<'w, ActiveLevel>>,
+    collider_status: Res<'w, MapGenerationColliderStatus>,
+    level_q: Query<'w, 's, &'static LevelMap>,
+}
+
+impl MapLoading<'_, '_> {
+    fn status(&self) -> String {
+        let Some(agreed_map) = &self.agreed_map else {
+            return "waiting for the map".to_string();
+        };
+        let title = find_map(&agreed_map.0.map).map_or(agreed_map.0.map.as_str(), |map| map.title);
+
+        if self.active_level.is_none() {
+            format!("loading map {title}")
+        } else if self.level_q.is_empty() || !self.level_q.iter().all(|level_map| level_map.markers_parsed) {
+            format!("spawning {title}")
+        } else if !self.collider_status.already_generated {
+            "building colliders".to_string()
+        } else {
+            "loading assets".to_string()
+        }
+    }
+}
+
+fn players_text(n: usize) -> String {
+    if n == 1 { "1 more player".to_string() } else { format!("{n} more players") }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_loading_status(
+    mut commands: Commands,
+    state: Res<State<AppState>>,
+    time: Res<Time>,
+    counter: Option<Res<ProgressCounter>>,
+    network: Res<NetworkConfig>,
+    lobby: Res<Lobby>,
+    socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    session: Option<Res<Session<Config>>>,
+    map_loading: MapLoading,
+    error: Option<Res<LoadingError>>,
+    mut times: ResMut<LoadingTimes>,
+    mut status_q: Query<&mut Text, (With<LoadingStatusText>, Without<LoadingHintText>)>,
+    mut hint_q: Query<&mut Text, (With<LoadingHintText>, Without<LoadingStatusText>)>,
+) {
+    let now = time.elapsed_seconds();
+    if let Some(counter) = &counter {
+        let done = counter.progress().done;
+        if done != times.done {
+            times.done = done;
+            times.changed = now;
+        }
+    }
+    let stalled = now - times.changed > STALL_TIMEOUT;
+
+    let (status, hint) = if let Some(error) = &error {
+        (error.0.clone(), "press escape to go back to the menu".to_string())
+    } else if *state.get() == AppState::Splash {
+        let status = if stalled { "loading takes long, some assets may be missing" } else { "loading" };
+        (status.to_string(), String::new())
+    } else if session.is_none() && network.offline {
+        ("starting the offline game".to_string(), String::new())
+    } else if session.is_none() {
+        let room = format!("room {}", network.room);
+        let status = match socket {
+            None => "connecting".to_string(),
+            Some(mut socket) => match socket.id() {
+                None if now - times.started > SERVER_TIMEOUT => {
+                    let message = format!("the signaling server {} can't be reached", network.server);
+                    error!("{message}");
+                    commands.insert_resource(LoadingError(message));
+                    format!("connecting to {}", network.server)
+                }
+                None => format!("connecting to {}", network.server),
+                Some(local_id) => {
+                    let missing_players = network.num_players.saturating_sub(lobby.sorted_players(local_id, network.role).len());
+                    let local_spectator = usize::from(network.role == PeerRole::Spectator);
+                    let missing_spectators = network.num_spectators.saturating_sub(lobby.sorted_spectators().len() + local_spectator);
+                    if missing_players > 0 {
+                        format!("waiting for {}", players_text(missing_players))
+                    } else if missing_spectators > 0 {
+                        format!("waiting for {missing_spectators} more spectators")
+                    } else {
+                        "meeting the other players".to_string()
+                    }
+                }
+            },
+        };
+        (status, room)
+    } else {
+        let status = map_loading.status();
+        if stalled {
+            let message = format!("loading timed out while {status}");
+            error!("{message}");
+            commands.insert_resource(LoadingError(message));
+        }
+        let hint = if network.offline { String::new() } else { format!("room {}", network.room) };
+        (status, hint)
+    };
+
+    for mut text in status_q.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+    for mut text in hint_q.iter_mut() {
+        if text.sections[0].value != hint {
+            text.sections[0].value = hint.clone();
+        }
+    }
+}
+
+/// the bar follows the progress smoothly, it jumps in big steps when a tracked system is done
+fn update_progress_bar(
+    time: Res<Time>,
+    counter: Option<Res<ProgressCounter>>,
+    error: Option<Res<LoadingError>>,
+    mut bar_q: Query<(&mut Style, &mut BackgroundColor), With<ProgressBar>>,
+) {
+    let progress = counter.map_or(0.0, |counter| {
+        let progress = counter.progress();
+        if progress.total == 0 { 0.0 } else { progress.done as f32 / progress.total as f32 }
+    });
+    let color = if error.is_some() { Color::rgb(0.8, 0.2, 0.15) } else { Color::rgb(0.85, 0.85, 0.85) };
+
+    for (mut style, mut background) in bar_q.iter_mut() {
+        let Val::Percent(width) = style.width else { continue };
+        let width = width + (progress * 100.0 - width) * (time.delta_seconds() * 8.0).min(1.0);
+        style.width = Val::Percent(width);
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+fn leave_after_error(
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    active_gamepad: Res<ActiveGamepad>,
+    error: Option<Res<LoadingError>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if error.is_none() {
+        return;
+    }
+    let back = active_gamepad
+        .gamepad
+        .is_some_and(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::East)));
+    if keys.just_pressed(KeyCode::Escape) || back {
+        app_state.set(AppState::MainMenu);
+    }
+}
+
+fn clear_loading_error(mut commands: Commands) {
+    commands.remove_resource::<LoadingError>();
+}
+
+/// a missing file stops loading right away, instead of after the stall timeout
+fn report_failed_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    watched: Res<WatchedAssets>,
+    error: Option<Res<LoadingError>>,
+) {
+    if error.is_some() {
+        return;
+    }
+    let Some(failed) = watched.0.iter().find(|handle| asset_server.load_state(handle.id()) == LoadState::Failed) else {
+        return;
+    };
+    let message = match failed.path() {
+        Some(path) => format!("{path} could not be loaded"),
+        None => "an asset could not be loaded".to_string(),
+    };
+    error!("{message}");
+    commands.insert_resource(LoadingError(message));
+}
+
+fn forget_watched_assets(mut watched: ResMut<WatchedAssets>) {
+    watched.0.clear();
+}
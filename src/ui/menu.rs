@@ -0,0 +1,382 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    despawn_screen,
+    game::NetworkConfig,
+    input::{
+        action_map::{Action, ActionInput},
+        gamepad::ActiveGamepad,
+    },
+    lobby::{room_code, PeerRole},
+    AppState,
+};
+
+use super::{console::{toggle_console, ConsoleState}, rebind::ControlsScreenState, settings::SettingsScreenState, MyUiAssets};
+
+/// The first screen after the loading screen, nothing is connected before a button is pressed here.
+/// In a match the same menu is shown over the game.
+pub struct MainMenuPlugin;
+
+impl Plugin for MainMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<MenuState>()
+            .add_event::<MenuButtonPressed>()
+            .init_resource::<MenuFocus>()
+            .init_resource::<RoomCodeInput>()
+            .add_systems(OnEnter(AppState::MainMenu), open_menu)
+            .add_systems(OnExit(AppState::MainMenu), close_menu)
+            .add_systems(OnEnter(MenuState::Main), main_page_setup)
+            .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainPage>)
+            .add_systems(OnEnter(MenuState::Join), join_page_setup)
+            .add_systems(OnExit(MenuState::Join), despawn_screen::<OnJoinPage>)
+            .add_systems(OnEnter(MenuState::Game), game_page_setup)
+            .add_systems(OnExit(MenuState::Game), despawn_screen::<OnGamePage>)
+            .add_systems(OnExit(AppState::InGame), close_menu)
+            .add_systems(Update, (
+                toggle_game_menu.run_if(in_state(AppState::InGame)),
+                navigate_menu,
+                menu_mouse,
+                type_room_code.run_if(in_state(MenuState::Join)),
+                activate_menu_button,
+                update_menu_buttons,
+            ).chain()
+                .after(toggle_console)
+                .run_if(in_state(AppState::MainMenu).or_else(in_state(AppState::InGame)))
+                .run_if(in_state(ControlsScreenState::Closed))
+                .run_if(in_state(SettingsScreenState::Closed))
+                .run_if(in_state(ConsoleState::Closed)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum MenuState {
+    #[default]
+    Disabled,
+    Main,
+    Join,
+    /// over the game, the simulation can't be paused in a p2p session and keeps running
+    Game,
+}
+
+#[derive(Component)]
+struct OnMainPage;
+
+#[derive(Component)]
+struct OnJoinPage;
+
+#[derive(Component)]
+struct OnGamePage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuButton {
+    Host,
+    Join,
+    Offline,
+    Settings,
+    Quit,
+    Connect,
+    Back,
+    Resume,
+    Leave,
+}
+
+/// a button of the current page, the index is the order for keyboard and gamepad navigation
+#[derive(Component)]
+struct MenuItem {
+    index: usize,
+    button: MenuButton,
+}
+
+#[derive(Event)]
+struct MenuButtonPressed(MenuButton);
+
+/// the item which is activated by enter or the south button
+#[derive(Resource, Default)]
+struct MenuFocus(usize);
+
+/// the room code which is typed on the join page
+#[derive(Resource, Default)]
+struct RoomCodeInput(String);
+
+#[derive(Component)]
+struct RoomCodeText;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const FOCUSED_BUTTON: Color = Color::rgb(0.3, 0.3, 0.3);
+
+fn open_menu(mut next_state: ResMut<NextState<MenuState>>) {
+    next_state.set(MenuState::Main);
+}
+
+fn close_menu(mut next_state: ResMut<NextState<MenuState>>) {
+    next_state.set(MenuState::Disabled);
+}
+
+fn text_style(ui_assets: &MyUiAssets, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: ui_assets.ui_font.clone(),
+        font_size,
+        color: Color::WHITE,
+    }
+}
+
+/// a full screen column with the logo on top
+fn spawn_page(
+    commands: &mut Commands,
+    ui_assets: &MyUiAssets,
+    background: Color,
+    marker: impl Component,
+    children: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                background_color: background.into(),
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                style: Style {
+                    width: Val::Px(300.0),
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+                image: UiImage::new(ui_assets.logo.clone()),
+                ..default()
+            });
+            children(parent);
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, ui_assets: &MyUiAssets, index: usize, button: MenuButton, label: &str) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(260.0),
+                    height: Val::Px(44.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            MenuItem { index, button },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(label, text_style(ui_assets, 26.0)));
+        });
+}
+
+fn main_page_setup(mut commands: Commands, ui_assets: Res<MyUiAssets>, mut focus: ResMut<MenuFocus>) {
+    focus.0 = 0;
+    spawn_page(&mut commands, &ui_assets, Color::NONE, OnMainPage, |parent| {
+        spawn_button(parent, &ui_assets, 0, MenuButton::Host, "Host");
+        spawn_button(parent, &ui_assets, 1, MenuButton::Join, "Join");
+        spawn_button(parent, &ui_assets, 2, MenuButton::Offline, "Play offline");
+        spawn_button(parent, &ui_assets, 3, MenuButton::Settings, "Settings");
+        spawn_button(parent, &ui_assets, 4, MenuButton::Quit, "Quit");
+    });
+}
+
+fn join_page_setup(
+    mut commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    mut focus: ResMut<MenuFocus>,
+    code_input: Res<RoomCodeInput>,
+) {
+    focus.0 = 0;
+    spawn_page(&mut commands, &ui_assets, Color::NONE, OnJoinPage, |parent| {
+        parent.spawn(TextBundle::from_section("Room code", text_style(&ui_assets, 22.0)));
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(260.0),
+                    height: Val::Px(44.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn((
+                    TextBundle::from_section(format!("{}_", code_input.0), text_style(&ui_assets, 26.0)),
+                    RoomCodeText,
+                ));
+            });
+        spawn_button(parent, &ui_assets, 0, MenuButton::Connect, "Connect");
+        spawn_button(parent, &ui_assets, 1, MenuButton::Back, "Back");
+    });
+}
+
+fn game_page_setup(mut commands: Commands, ui_assets: Res<MyUiAssets>, mut focus: ResMut<MenuFocus>) {
+    focus.0 = 0;
+    spawn_page(&mut commands, &ui_assets, Color::rgba(0.0, 0.0, 0.0, 0.6), OnGamePage, |parent| {
+        spawn_button(parent, &ui_assets, 0, MenuButton::Resume, "Resume");
+        spawn_button(parent, &ui_assets, 1, MenuButton::Settings, "Settings");
+        spawn_button(parent, &ui_assets, 2, MenuButton::Leave, "Leave match");
+        spawn_button(parent, &ui_assets, 3, MenuButton::Quit, "Quit");
+    });
+}
+
+/// the menu key opens and closes the menu over the game
+fn toggle_game_menu(actions: ActionInput, menu_state: Res<State<MenuState>>, mut next_state: ResMut<NextState<MenuState>>) {
+    if !actions.just_pressed(Action::Menu) {
+        return;
+    }
+    match menu_state.get() {
+        MenuState::Disabled => next_state.set(MenuState::Game),
+        MenuState::Game => next_state.set(MenuState::Disabled),
+        _ => {}
+    }
+}
+
+/// arrow keys or the d-pad move the focus, enter or south press the focused button
+fn navigate_menu(
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    active_gamepad: Res<ActiveGamepad>,
+    menu_state: Res<State<MenuState>>,
+    item_q: Query<&MenuItem>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<MenuButtonPressed>,
+) {
+    let pad = |button_type| {
+        active_gamepad
+            .gamepad
+            .is_some_and(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    };
+
+    let count = item_q.iter().count();
+    if count == 0 {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Up) || pad(GamepadButtonType::DPadUp) {
+        focus.0 = (focus.0 + count - 1) % count;
+    }
+    if keys.just_pressed(KeyCode::Down) || keys.just_pressed(KeyCode::Tab) || pad(GamepadButtonType::DPadDown) {
+        focus.0 = (focus.0 + 1) % count;
+    }
+
+    if keys.just_pressed(KeyCode::Return) || pad(GamepadButtonType::South) {
+        if let Some(item) = item_q.iter().find(|item| item.index == focus.0) {
+            pressed.send(MenuButtonPressed(item.button));
+        }
+    }
+    if (keys.just_pressed(KeyCode::Escape) || pad(GamepadButtonType::East)) && menu_state.get() == &MenuState::Join {
+        pressed.send(MenuButtonPressed(MenuButton::Back));
+    }
+    // escape is the menu key in the game, see `toggle_game_menu`
+    if pad(GamepadButtonType::East) && menu_state.get() == &MenuState::Game {
+        pressed.send(MenuButtonPressed(MenuButton::Resume));
+    }
+}
+
+fn menu_mouse(
+    interaction_q: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<MenuButtonPressed>,
+) {
+    for (interaction, item) in interaction_q.iter() {
+        match interaction {
+            Interaction::Pressed => pressed.send(MenuButtonPressed(item.button)),
+            Interaction::Hovered => focus.0 = item.index,
+            Interaction::None => {}
+        }
+    }
+}
+
+fn type_room_code(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut code_input: ResMut<RoomCodeInput>,
+    mut text_q: Query<&mut Text, With<RoomCodeText>>,
+) {
+    for character in characters.read() {
+        if character.char.is_ascii_alphanumeric() && code_input.0.len() < 32 {
+            code_input.0.push(character.char.to_ascii_lowercase());
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        code_input.0.pop();
+    }
+
+    if code_input.is_changed() {
+        for mut text in text_q.iter_mut() {
+            text.sections[0].value = format!("{}_", code_input.0);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn activate_menu_button(
+    mut pressed: EventReader<MenuButtonPressed>,
+    mut network: ResMut<NetworkConfig>,
+    code_input: Res<RoomCodeInput>,
+    time: Res<Time>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut settings_state: ResMut<NextState<SettingsScreenState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for MenuButtonPressed(button) in pressed.read() {
+        match button {
+            MenuButton::Host => {
+                // the room code only brings the peers together, the map is still decided by the first player
+                network.room = room_code(time.elapsed().as_nanos() as u64);
+                network.role = PeerRole::Player;
+                network.offline = false;
+                info!("hosting room {}", network.room);
+                app_state.set(AppState::GameLoading);
+            }
+            MenuButton::Join => menu_state.set(MenuState::Join),
+            MenuButton::Connect => {
+                if code_input.0.is_empty() {
+                    continue;
+                }
+                network.room = code_input.0.clone();
+                network.offline = false;
+                app_state.set(AppState::GameLoading);
+            }
+            MenuButton::Offline => {
+                network.offline = true;
+                app_state.set(AppState::GameLoading);
+            }
+            MenuButton::Settings => settings_state.set(SettingsScreenState::Open),
+            MenuButton::Quit => exit.send(AppExit),
+            MenuButton::Back => menu_state.set(MenuState::Main),
+            MenuButton::Resume => menu_state.set(MenuState::Disabled),
+            // the session, the players and the level are removed when the main menu is entered
+            MenuButton::Leave => {
+                info!("leaving the match");
+                app_state.set(AppState::MainMenu);
+            }
+        }
+    }
+}
+
+fn update_menu_buttons(focus: Res<MenuFocus>, mut item_q: Query<(&MenuItem, &mut BackgroundColor)>) {
+    for (item, mut color) in item_q.iter_mut() {
+        let wanted = if item.index == focus.0 { FOCUSED_BUTTON } else { NORMAL_BUTTON };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use bevy::prelude::*;
-use iyes_progress::prelude::AssetsLoading;
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use self::{console::ConsoleState, loading::TrackedLoading, menu::MenuState, rebind::ControlsScreenState, settings::SettingsScreenState};
 
 #[derive(Resource)]
 pub struct MyUiAssets {
@@ -11,7 +12,7 @@ pub fn load_ui_assets(
     mut commands: Commands,
     ass: Res<AssetServer>,
     // we need to add our handles here, to track their loading progress:
-    mut loading: ResMut<AssetsLoading>,
+    mut loading: TrackedLoading,
 ) {
     let ui_font = ass.load("quicksand-light.ttf");
     let logo = ass.load("logo_1.png");
@@ -24,4 +25,34 @@ pub fn load_ui_assets(
     commands.insert_resource(MyUiAssets { ui_font, logo });
 }
 
-pub mod splash;
\ No newline at end of file
+pub mod console;
+pub mod loading;
+pub mod gamepad;
+pub mod menu;
+pub mod hud;
+pub mod nameplate;
+pub mod rebind;
+pub mod settings;
+
+/// The screens which are shown over the game. While one is open the cursor is free and the
+/// local player gets no input, the simulation keeps running.
+#[derive(SystemParam)]
+pub struct OpenScreens<'w> {
+    controls: Res<'w, State<ControlsScreenState>>,
+    settings: Res<'w, State<SettingsScreenState>>,
+    menu: Res<'w, State<MenuState>>,
+    console: Res<'w, State<ConsoleState>>,
+}
+
+impl OpenScreens<'_> {
+    pub fn any(&self) -> bool {
+        self.controls.get() == &ControlsScreenState::Open
+            || self.settings.get() == &SettingsScreenState::Open
+            || self.menu.get() == &MenuState::Game
+            || self.console.get() == &ConsoleState::Open
+    }
+}
+
+pub fn screen_open(screens: OpenScreens) -> bool {
+    screens.any()
+}
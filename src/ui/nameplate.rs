@@ -0,0 +1,191 @@
+//! Names and health above the other players, so you can tell who is who.
+//!
+//! The plates are ui nodes which follow the head of their player on screen. They fade out with the
+//! distance and when something solid is between the camera and the head. The local player has none.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    despawn_screen,
+    lobby::PlayerNames,
+    map::colliders,
+    player::{Head, MainPlayer, Player, PlayerStats},
+    AppState,
+};
+
+use super::MyUiAssets;
+
+/// the plate is this far above the head bone
+const HEAD_OFFSET: f32 = 0.45;
+const PLATE_WIDTH: f32 = 160.0;
+const PLATE_HEIGHT: f32 = 34.0;
+/// plates start to fade at this distance and are gone at the end
+const FADE_START: f32 = 12.0;
+const FADE_END: f32 = 30.0;
+/// alpha change per second, so a plate doesn't blink when a player walks past a pillar
+const FADE_SPEED: f32 = 6.0;
+
+pub struct NameplatePlugin;
+
+impl Plugin for NameplatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (spawn_nameplates, update_nameplates).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), despawn_screen::<Nameplate>);
+    }
+}
+
+#[derive(Component)]
+pub struct Nameplate {
+    player: Entity,
+    text: Entity,
+    health: Entity,
+    alpha: f32,
+}
+
+fn spawn_nameplates(
+    mut commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    player_q: Query<(Entity, &Player), Without<MainPlayer>>,
+    plate_q: Query<&Nameplate>,
+) {
+    for (e, player) in player_q.iter() {
+        if player.head.is_none() || plate_q.iter().any(|plate| plate.player == e) {
+            continue;
+        }
+
+        let mut text = Entity::PLACEHOLDER;
+        let mut health = Entity::PLACEHOLDER;
+        let mut plate = commands.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(PLATE_WIDTH),
+                height: Val::Px(PLATE_HEIGHT),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::End,
+                row_gap: Val::Px(3.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        });
+        plate.with_children(|parent| {
+            text = parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: ui_assets.ui_font.clone(),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .id();
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(70.0),
+                        height: Val::Px(6.0),
+                        padding: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    health = parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::rgb(0.75, 0.12, 0.1).into(),
+                            ..default()
+                        })
+                        .id();
+                });
+        });
+        plate.insert(Nameplate {
+            player: e,
+            text,
+            health,
+            alpha: 0.0,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_nameplates(
+    mut commands: Commands,
+    time: Res<Time>,
+    names: Res<PlayerNames>,
+    rapier: Res<RapierContext>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    player_q: Query<(&Player, &PlayerStats, Has<MainPlayer>)>,
+    head_q: Query<&GlobalTransform, With<Head>>,
+    mut plate_q: Query<(Entity, &mut Nameplate, &mut Style, &mut Visibility)>,
+    mut text_q: Query<&mut Text>,
+    mut health_q: Query<(&mut Style, &mut BackgroundColor), Without<Nameplate>>,
+) {
+    let camera = camera_q.iter().find(|(camera, _)| camera.is_active);
+
+    for (e, mut plate, mut style, mut visibility) in plate_q.iter_mut() {
+        let Ok((player, stats, local)) = player_q.get(plate.player) else {
+            commands.entity(e).despawn_recursive();
+            continue;
+        };
+
+        // the local player can become the main player after its plate was spawned
+        let position = player
+            .head
+            .and_then(|head| head_q.get(head).ok())
+            .map(|head| head.translation() + Vec3::Y * HEAD_OFFSET)
+            .filter(|_| !local);
+        let screen = camera.zip(position).and_then(|((camera, camera_transform), position)| {
+            let screen = camera.world_to_viewport(camera_transform, position)?;
+            Some((screen, camera_transform.translation(), position))
+        });
+
+        let target = match screen {
+            Some((_, origin, position)) => {
+                let distance = origin.distance(position);
+                let direction = (position - origin) / distance.max(0.001);
+                let occluded = rapier
+                    .cast_ray(origin, direction, distance, true, colliders::camera_filter())
+                    .is_some_and(|(_, toi)| toi < distance - 0.2);
+                if occluded {
+                    0.0
+                } else {
+                    1.0 - ((distance - FADE_START) / (FADE_END - FADE_START)).clamp(0.0, 1.0)
+                }
+            }
+            None => 0.0,
+        };
+        let step = FADE_SPEED * time.delta_seconds();
+        plate.alpha = if screen.is_none() { 0.0 } else { plate.alpha + (target - plate.alpha).clamp(-step, step) };
+
+        let wanted = if plate.alpha > 0.01 { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+        let Some((screen, _, _)) = screen.filter(|_| plate.alpha > 0.01) else { continue };
+
+        style.left = Val::Px(screen.x - PLATE_WIDTH / 2.0);
+        style.top = Val::Px(screen.y - PLATE_HEIGHT);
+
+        if let Ok(mut text) = text_q.get_mut(plate.text) {
+            let name = names.get(player.handle);
+            if text.sections[0].value != name {
+                text.sections[0].value = name;
+            }
+            text.sections[0].style.color.set_a(plate.alpha);
+        }
+        if let Ok((mut health_style, mut color)) = health_q.get_mut(plate.health) {
+            let fraction = if stats.max_health > 0.0 { (stats.health / stats.max_health).clamp(0.0, 1.0) } else { 0.0 };
+            health_style.width = Val::Percent(fraction * 100.0);
+            color.0.set_a(plate.alpha);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+
+use crate::{despawn_screen, input::action_map::{Action, ActionInput, ActionMap, Binding, BINDING_SLOTS}};
+
+use super::{console::ConsoleState, MyUiAssets};
+
+pub struct RebindPlugin;
+
+impl Plugin for RebindPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<ControlsScreenState>()
+            .init_resource::<RebindListening>()
+            .add_systems(Update, toggle_controls_screen.run_if(in_state(ConsoleState::Closed)))
+            .add_systems(OnEnter(ControlsScreenState::Open), controls_screen_setup)
+            .add_systems(OnExit(ControlsScreenState::Open), (despawn_screen::<OnControlsScreen>, save_bindings))
+            .add_systems(Update, (
+                controls_button_system,
+                capture_binding.after(controls_button_system),
+                rebuild_controls_screen.after(capture_binding),
+            ).run_if(in_state(ControlsScreenState::Open)).run_if(in_state(ConsoleState::Closed)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum ControlsScreenState {
+    #[default]
+    Closed,
+    Open,
+}
+
+#[derive(Component)]
+pub struct OnControlsScreen;
+
+#[derive(Component, Clone, Copy)]
+enum ControlsButton {
+    Slot(Action, usize),
+    Reset,
+    Close,
+}
+
+/// the binding slot which waits for the next pressed key / button
+#[derive(Resource, Default)]
+struct RebindListening {
+    slot: Option<(Action, usize)>,
+    // the click on the slot itself must not become the new binding
+    wait_for_release: bool,
+    message: Option<String>,
+}
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const LISTENING_BUTTON: Color = Color::rgb(0.35, 0.35, 0.1);
+const CONFLICT_COLOR: Color = Color::rgb(0.9, 0.3, 0.3);
+
+fn toggle_controls_screen(
+    actions: ActionInput,
+    listening: Res<RebindListening>,
+    state: Res<State<ControlsScreenState>>,
+    mut next_state: ResMut<NextState<ControlsScreenState>>,
+) {
+    if listening.slot.is_some() || !actions.just_pressed(Action::OpenControls) {
+        return;
+    }
+
+    next_state.set(match state.get() {
+        ControlsScreenState::Closed => ControlsScreenState::Open,
+        ControlsScreenState::Open => ControlsScreenState::Closed,
+    });
+}
+
+fn save_bindings(action_map: Res<ActionMap>, mut listening: ResMut<RebindListening>) {
+    *listening = RebindListening::default();
+    action_map.save();
+}
+
+fn controls_screen_setup(
+    commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    action_map: Res<ActionMap>,
+    listening: Res<RebindListening>,
+) {
+    spawn_controls_screen(commands, &ui_assets, &action_map, &listening);
+}
+
+fn rebuild_controls_screen(
+    mut commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    action_map: Res<ActionMap>,
+    listening: Res<RebindListening>,
+    screen_q: Query<Entity, With<OnControlsScreen>>,
+) {
+    if !action_map.is_changed() && !listening.is_changed() {
+        return;
+    }
+
+    for e in screen_q.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    spawn_controls_screen(commands, &ui_assets, &action_map, &listening);
+}
+
+fn spawn_controls_screen(
+    mut commands: Commands,
+    ui_assets: &MyUiAssets,
+    action_map: &ActionMap,
+    listening: &RebindListening,
+) {
+    let text_style = TextStyle {
+        font: ui_assets.ui_font.clone(),
+        font_size: 22.0,
+        color: Color::WHITE,
+    };
+
+    let button_style = Style {
+        width: Val::Px(200.0),
+        height: Val::Px(32.0),
+        margin: UiRect::horizontal(Val::Px(4.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            OnControlsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section("Controls", TextStyle { font_size: 40.0, ..text_style.clone() })
+                    .with_style(Style { margin: UiRect::bottom(Val::Px(20.0)), ..default() }),
+            );
+
+            for action in Action::ALL {
+                let bindings = action_map.get(action);
+                let label_color = if action_map.has_conflicts(action) { CONFLICT_COLOR } else { Color::WHITE };
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::vertical(Val::Px(2.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(
+                            TextBundle::from_section(action.label(), TextStyle { color: label_color, ..text_style.clone() })
+                                .with_style(Style { width: Val::Px(180.0), ..default() }),
+                        );
+
+                        for slot in 0..BINDING_SLOTS {
+                            let is_listening = listening.slot == Some((action, slot));
+                            let (label, color) = match bindings.get(slot) {
+                                _ if is_listening => ("press a key...".to_string(), Color::WHITE),
+                                Some(binding) if !action_map.conflicts(action, *binding).is_empty() => {
+                                    (binding.label(), CONFLICT_COLOR)
+                                }
+                                Some(binding) => (binding.label(), Color::WHITE),
+                                None => ("-".to_string(), Color::GRAY),
+                            };
+
+                            row.spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: if is_listening { LISTENING_BUTTON } else { NORMAL_BUTTON }.into(),
+                                    ..default()
+                                },
+                                ControlsButton::Slot(action, slot),
+                            ))
+                            .with_children(|button| {
+                                button.spawn(TextBundle::from_section(label, TextStyle { color, ..text_style.clone() }));
+                            });
+                        }
+                    });
+            }
+
+            let message = match &listening.message {
+                Some(message) => message.clone(),
+                None if listening.slot.is_some() => "Escape cancels, Delete clears the slot".to_string(),
+                None => String::new(),
+            };
+            parent.spawn(
+                TextBundle::from_section(message, TextStyle { color: CONFLICT_COLOR, ..text_style.clone() })
+                    .with_style(Style { margin: UiRect::vertical(Val::Px(12.0)), ..default() }),
+            );
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style { flex_direction: FlexDirection::Row, ..default() },
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (button, label) in [(ControlsButton::Reset, "Reset defaults"), (ControlsButton::Close, "Save & close")] {
+                        row.spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            button,
+                        ))
+                        .with_children(|b| {
+                            b.spawn(TextBundle::from_section(label, text_style.clone()));
+                        });
+                    }
+                });
+        });
+}
+
+fn controls_button_system(
+    mut interaction_q: Query<(&Interaction, &ControlsButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut listening: ResMut<RebindListening>,
+    mut action_map: ResMut<ActionMap>,
+    mut next_state: ResMut<NextState<ControlsScreenState>>,
+) {
+    if listening.slot.is_some() {
+        return;
+    }
+
+    for (interaction, button, mut color) in interaction_q.iter_mut() {
+        match interaction {
+            Interaction::Pressed => match button {
+                ControlsButton::Slot(action, slot) => {
+                    listening.slot = Some((*action, *slot));
+                    listening.wait_for_release = true;
+                    listening.message = None;
+                }
+                ControlsButton::Reset => {
+                    *action_map = ActionMap::default();
+                    listening.message = None;
+                }
+                ControlsButton::Close => next_state.set(ControlsScreenState::Closed),
+            },
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+fn capture_binding(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut listening: ResMut<RebindListening>,
+    mut action_map: ResMut<ActionMap>,
+) {
+    let Some((action, slot)) = listening.slot else { return };
+
+    if listening.wait_for_release {
+        if mouse.get_pressed().next().is_none() {
+            listening.wait_for_release = false;
+        }
+        return;
+    }
+
+    let binding = if let Some(key) = keys.get_just_pressed().next() {
+        match key {
+            KeyCode::Escape => {
+                listening.slot = None;
+                return;
+            }
+            KeyCode::Delete | KeyCode::Back => {
+                action_map.unbind(action, slot);
+                listening.slot = None;
+                return;
+            }
+            key => Binding::Key(*key),
+        }
+    } else if let Some(button) = mouse.get_just_pressed().next() {
+        Binding::Mouse(*button)
+    } else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+        Binding::Gamepad(button.button_type)
+    } else {
+        return;
+    };
+
+    action_map.bind(action, slot, binding);
+
+    let conflicts = action_map.conflicts(action, binding);
+    listening.message = if conflicts.is_empty() {
+        None
+    } else {
+        let names: Vec<_> = conflicts.iter().map(|a| a.label()).collect();
+        Some(format!("{} is also bound to: {}", binding.label(), names.join(", ")))
+    };
+    listening.slot = None;
+}
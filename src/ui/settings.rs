@@ -0,0 +1,363 @@
+//! The settings screen, opened from the main menu and in a match.
+//!
+//! Every change goes straight into the `Settings` resource, so it is applied while the screen is
+//! open. The file is saved when the screen is closed.
+
+use bevy::prelude::*;
+
+use crate::{
+    despawn_screen,
+    input::gamepad::ActiveGamepad,
+    settings::{Settings, ShadowQuality, WindowModeSetting, RESOLUTIONS},
+};
+
+use super::{console::ConsoleState, rebind::ControlsScreenState, MyUiAssets};
+
+pub struct SettingsScreenPlugin;
+
+impl Plugin for SettingsScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<SettingsScreenState>()
+            .init_resource::<SettingsFocus>()
+            .add_systems(OnEnter(SettingsScreenState::Open), settings_screen_setup)
+            .add_systems(OnExit(SettingsScreenState::Open), (despawn_screen::<OnSettingsScreen>, save_settings))
+            .add_systems(Update, (
+                navigate_settings,
+                settings_mouse,
+                type_name,
+                update_settings_screen,
+            ).chain()
+                .run_if(in_state(SettingsScreenState::Open))
+                .run_if(in_state(ControlsScreenState::Closed))
+                .run_if(in_state(ConsoleState::Closed)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+pub enum SettingsScreenState {
+    #[default]
+    Closed,
+    Open,
+}
+
+#[derive(Component)]
+struct OnSettingsScreen;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingRow {
+    WindowMode,
+    Resolution,
+    Vsync,
+    Shadows,
+    Fov,
+    MouseSensitivity,
+    InvertY,
+    MasterVolume,
+    EffectsVolume,
+    Name,
+    InputDelay,
+    Controls,
+    Back,
+}
+
+impl SettingRow {
+    const ALL: [SettingRow; 13] = [
+        SettingRow::WindowMode,
+        SettingRow::Resolution,
+        SettingRow::Vsync,
+        SettingRow::Shadows,
+        SettingRow::Fov,
+        SettingRow::MouseSensitivity,
+        SettingRow::InvertY,
+        SettingRow::MasterVolume,
+        SettingRow::EffectsVolume,
+        SettingRow::Name,
+        SettingRow::InputDelay,
+        SettingRow::Controls,
+        SettingRow::Back,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingRow::WindowMode => "Window",
+            SettingRow::Resolution => "Resolution",
+            SettingRow::Vsync => "VSync",
+            SettingRow::Shadows => "Shadows",
+            SettingRow::Fov => "Field of view",
+            SettingRow::MouseSensitivity => "Mouse sensitivity",
+            SettingRow::InvertY => "Invert Y",
+            SettingRow::MasterVolume => "Volume",
+            SettingRow::EffectsVolume => "Effects volume",
+            SettingRow::Name => "Name",
+            SettingRow::InputDelay => "Input delay",
+            SettingRow::Controls => "Controls...",
+            SettingRow::Back => "Back",
+        }
+    }
+
+    fn value(self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+        let percent = |volume: f32| format!("{:.0}%", volume * 100.0);
+        match self {
+            SettingRow::WindowMode => format!("{:?}", settings.window_mode),
+            SettingRow::Resolution => format!("{} x {}", settings.resolution.0, settings.resolution.1),
+            SettingRow::Vsync => on_off(settings.vsync),
+            SettingRow::Shadows => format!("{:?}", settings.shadows),
+            SettingRow::Fov => format!("{:.0}°", settings.fov),
+            SettingRow::MouseSensitivity => format!("{:.2}", settings.mouse_sensitivity),
+            SettingRow::InvertY => on_off(settings.invert_y),
+            SettingRow::MasterVolume => percent(settings.master_volume),
+            SettingRow::EffectsVolume => percent(settings.effects_volume),
+            SettingRow::Name => settings.name.clone(),
+            SettingRow::InputDelay => format!("{} frames", settings.input_delay),
+            SettingRow::Controls | SettingRow::Back => String::new(),
+        }
+    }
+
+    /// changes the setting one step up or down, `false` if the row has no value to change
+    fn adjust(self, settings: &mut Settings, step: i32) -> bool {
+        let float = |value: f32, by: f32, min: f32, max: f32| ((value + by * step as f32).clamp(min, max) * 100.0).round() / 100.0;
+        match self {
+            SettingRow::WindowMode => {
+                let modes = [WindowModeSetting::Windowed, WindowModeSetting::Borderless, WindowModeSetting::Fullscreen];
+                settings.window_mode = cycle(&modes, settings.window_mode, step);
+            }
+            SettingRow::Resolution => settings.resolution = cycle(RESOLUTIONS, settings.resolution, step),
+            SettingRow::Vsync => settings.vsync = !settings.vsync,
+            SettingRow::Shadows => {
+                let qualities = [ShadowQuality::Off, ShadowQuality::Low, ShadowQuality::Medium, ShadowQuality::High];
+                settings.shadows = cycle(&qualities, settings.shadows, step);
+            }
+            SettingRow::Fov => settings.fov = float(settings.fov, 5.0, 30.0, 100.0),
+            SettingRow::MouseSensitivity => settings.mouse_sensitivity = float(settings.mouse_sensitivity, 0.25, 0.25, 8.0),
+            SettingRow::InvertY => settings.invert_y = !settings.invert_y,
+            SettingRow::MasterVolume => settings.master_volume = float(settings.master_volume, 0.1, 0.0, 1.0),
+            SettingRow::EffectsVolume => settings.effects_volume = float(settings.effects_volume, 0.1, 0.0, 1.0),
+            SettingRow::InputDelay => settings.input_delay = (settings.input_delay as i32 + step).clamp(0, 8) as usize,
+            SettingRow::Name | SettingRow::Controls | SettingRow::Back => return false,
+        }
+        true
+    }
+}
+
+/// the next value of a list, values which are not in the list start at the first
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T, step: i32) -> T {
+    let index = values.iter().position(|value| *value == current).map_or(0, |i| i as i32 + step);
+    values[index.rem_euclid(values.len() as i32) as usize]
+}
+
+#[derive(Component)]
+struct SettingsItem {
+    index: usize,
+    row: SettingRow,
+}
+
+#[derive(Component)]
+struct SettingValueText(SettingRow);
+
+#[derive(Resource, Default)]
+struct SettingsFocus(usize);
+
+const NORMAL_ROW: Color = Color::rgba(0.15, 0.15, 0.15, 0.9);
+const FOCUSED_ROW: Color = Color::rgba(0.3, 0.3, 0.3, 0.9);
+
+fn settings_screen_setup(
+    mut commands: Commands,
+    ui_assets: Res<MyUiAssets>,
+    settings: Res<Settings>,
+    mut focus: ResMut<SettingsFocus>,
+) {
+    focus.0 = 0;
+    let text_style = |font_size| TextStyle {
+        font: ui_assets.ui_font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                // below the controls screen, which is opened from here
+                z_index: ZIndex::Global(9),
+                ..default()
+            },
+            OnSettingsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section("Settings", text_style(40.0))
+                    .with_style(Style { margin: UiRect::bottom(Val::Px(20.0)), ..default() }),
+            );
+
+            for (index, row) in SettingRow::ALL.into_iter().enumerate() {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(480.0),
+                                height: Val::Px(34.0),
+                                padding: UiRect::horizontal(Val::Px(12.0)),
+                                justify_content: JustifyContent::SpaceBetween,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_ROW.into(),
+                            ..default()
+                        },
+                        SettingsItem { index, row },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(row.label(), text_style(22.0)));
+                        parent.spawn((TextBundle::from_section(row.value(&settings), text_style(22.0)), SettingValueText(row)));
+                    });
+            }
+
+            parent.spawn(
+                TextBundle::from_section("arrows change a setting, type to change the name, escape closes", text_style(16.0))
+                    .with_style(Style { margin: UiRect::top(Val::Px(12.0)), ..default() }),
+            );
+        });
+}
+
+fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+fn activate_row(
+    row: SettingRow,
+    settings: &mut Settings,
+    settings_state: &mut NextState<SettingsScreenState>,
+    controls_state: &mut NextState<ControlsScreenState>,
+) {
+    match row {
+        SettingRow::Controls => controls_state.set(ControlsScreenState::Open),
+        SettingRow::Back => settings_state.set(SettingsScreenState::Closed),
+        _ => {
+            row.adjust(settings, 1);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn navigate_settings(
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    active_gamepad: Res<ActiveGamepad>,
+    item_q: Query<&SettingsItem>,
+    mut focus: ResMut<SettingsFocus>,
+    mut settings: ResMut<Settings>,
+    mut settings_state: ResMut<NextState<SettingsScreenState>>,
+    mut controls_state: ResMut<NextState<ControlsScreenState>>,
+) {
+    let pad = |button_type| {
+        active_gamepad
+            .gamepad
+            .is_some_and(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    };
+
+    let count = item_q.iter().count();
+    if count == 0 {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Up) || pad(GamepadButtonType::DPadUp) {
+        focus.0 = (focus.0 + count - 1) % count;
+    }
+    if keys.just_pressed(KeyCode::Down) || keys.just_pressed(KeyCode::Tab) || pad(GamepadButtonType::DPadDown) {
+        focus.0 = (focus.0 + 1) % count;
+    }
+
+    let Some(item) = item_q.iter().find(|item| item.index == focus.0) else { return };
+    let step = if keys.just_pressed(KeyCode::Left) || pad(GamepadButtonType::DPadLeft) {
+        -1
+    } else if keys.just_pressed(KeyCode::Right) || pad(GamepadButtonType::DPadRight) {
+        1
+    } else {
+        0
+    };
+    if step != 0 {
+        item.row.adjust(&mut settings, step);
+    }
+
+    if keys.just_pressed(KeyCode::Return) || pad(GamepadButtonType::South) {
+        activate_row(item.row, &mut settings, &mut settings_state, &mut controls_state);
+    }
+    if keys.just_pressed(KeyCode::Escape) || pad(GamepadButtonType::East) {
+        settings_state.set(SettingsScreenState::Closed);
+    }
+}
+
+fn settings_mouse(
+    interaction_q: Query<(&Interaction, &SettingsItem), Changed<Interaction>>,
+    mut focus: ResMut<SettingsFocus>,
+    mut settings: ResMut<Settings>,
+    mut settings_state: ResMut<NextState<SettingsScreenState>>,
+    mut controls_state: ResMut<NextState<ControlsScreenState>>,
+) {
+    for (interaction, item) in interaction_q.iter() {
+        match interaction {
+            Interaction::Pressed => activate_row(item.row, &mut settings, &mut settings_state, &mut controls_state),
+            Interaction::Hovered => focus.0 = item.index,
+            Interaction::None => {}
+        }
+    }
+}
+
+/// the name is typed while its row has the focus
+fn type_name(
+    keys: Res<Input<KeyCode>>,
+    focus: Res<SettingsFocus>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut settings: ResMut<Settings>,
+) {
+    if SettingRow::ALL.get(focus.0) != Some(&SettingRow::Name) {
+        characters.clear();
+        return;
+    }
+    for character in characters.read() {
+        let c = character.char;
+        if (c.is_alphanumeric() || c == ' ' || c == '-' || c == '_') && settings.name.chars().count() < 16 {
+            settings.name.push(c);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        settings.name.pop();
+    }
+}
+
+fn update_settings_screen(
+    settings: Res<Settings>,
+    focus: Res<SettingsFocus>,
+    mut item_q: Query<(&SettingsItem, &mut BackgroundColor)>,
+    mut value_q: Query<(&SettingValueText, &mut Text)>,
+) {
+    for (item, mut color) in item_q.iter_mut() {
+        let wanted = if item.index == focus.0 { FOCUSED_ROW } else { NORMAL_ROW };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+
+    if !settings.is_changed() && !focus.is_changed() {
+        return;
+    }
+    for (SettingValueText(row), mut text) in value_q.iter_mut() {
+        let mut value = row.value(&settings);
+        if *row == SettingRow::Name && SettingRow::ALL.get(focus.0) == Some(row) {
+            value.push('_');
+        }
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}